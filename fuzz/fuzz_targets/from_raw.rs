@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tw_nhi_icc_service::{NHICardBasic, NameDecoding};
+
+fuzz_target!(|data: &[u8]| {
+    let lenient = NameDecoding {
+        lenient: true,
+        ..NameDecoding::default()
+    };
+
+    let _ = NHICardBasic::from_raw_with_name_decoding(data, &lenient);
+
+    if let Ok(card) = NHICardBasic::from_raw(data) {
+        let _ = card.masked();
+
+        // a parsed card which can be encoded again must be parsed to the same card
+        if let Ok(raw) = card.to_raw() {
+            let card_again = NHICardBasic::from_raw(raw).unwrap();
+
+            assert_eq!(card.card_no, card_again.card_no);
+            assert_eq!(card.full_name, card_again.full_name);
+            assert_eq!(card.id_no, card_again.id_no);
+            assert_eq!(card.birth_date, card_again.birth_date);
+            assert_eq!(card.sex, card_again.sex);
+            assert_eq!(card.issue_date, card_again.issue_date);
+        }
+    }
+});
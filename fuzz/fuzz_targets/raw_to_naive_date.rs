@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tw_nhi_icc_service::NHICardBasic;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = NHICardBasic::raw_to_naive_date(data);
+});
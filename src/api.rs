@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// The response of `GET /version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub pre:   String,
+    pub text:  String,
+}
+
+/// The body of an error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+/// The body of `POST /readers/{name}/apdu`. Each APDU is a hex string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApduRequest {
+    pub apdus: Vec<String>,
+}
+
+/// The response of an APDU, in hex.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApduResult {
+    pub data: String,
+    /// SW1 and SW2, e.g. `9000`.
+    pub sw:   String,
+}
+
+impl ApduResult {
+    /// Splits the status words from a response.
+    pub fn from_response(response: &[u8]) -> Self {
+        let (data, sw) = response.split_at(response.len().saturating_sub(2));
+
+        Self {
+            data: hex::encode_upper(data), sw: hex::encode_upper(sw)
+        }
+    }
+}
+
+/// The response of `POST /readers/{name}/apdu`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApduResponse {
+    pub responses: Vec<ApduResult>,
+}
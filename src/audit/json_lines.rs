@@ -0,0 +1,110 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use chrono::prelude::*;
+
+use super::{AuditQuery, AuditRecord};
+
+#[derive(Debug)]
+pub(super) struct JsonLinesStore {
+    path: PathBuf,
+    file: File,
+}
+
+impl JsonLinesStore {
+    #[inline]
+    fn open_append(path: &PathBuf) -> anyhow::Result<File> {
+        Ok(OpenOptions::new().create(true).append(true).open(path)?)
+    }
+
+    pub(super) fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let file = Self::open_append(&path)?;
+
+        Ok(Self {
+            path,
+            file,
+        })
+    }
+
+    pub(super) fn append(&mut self, record: &AuditRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+
+    fn read_all(&self) -> anyhow::Result<Vec<AuditRecord>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+
+        let mut records = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(error) => {
+                    tracing::warn!(target: "audit", line = index + 1, ?error, "invalid record");
+                },
+            }
+        }
+
+        Ok(records)
+    }
+
+    pub(super) fn query(&mut self, query: &AuditQuery) -> anyhow::Result<Vec<AuditRecord>> {
+        let mut records =
+            self.read_all()?.into_iter().filter(|record| query.matches(record)).collect::<Vec<_>>();
+
+        let limit = query.limit();
+
+        if records.len() > limit {
+            records.drain(..records.len() - limit);
+        }
+
+        Ok(records)
+    }
+
+    pub(super) fn prune(&mut self, before: DateTime<Local>) -> anyhow::Result<usize> {
+        let records = self.read_all()?;
+        let count = records.len();
+
+        let records =
+            records.into_iter().filter(|record| record.time >= before).collect::<Vec<_>>();
+        let removed = count - records.len();
+
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+
+        {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+
+            writer.flush()?;
+        }
+
+        fs::rename(&temp_path, &self.path)?;
+
+        self.file = Self::open_append(&self.path)?;
+
+        Ok(removed)
+    }
+}
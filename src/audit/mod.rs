@@ -0,0 +1,432 @@
+mod json_lines;
+#[cfg(feature = "audit-sqlite")]
+mod sqlite;
+
+use std::{
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use chrono::prelude::*;
+use clap::ValueEnum;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc},
+    task, time,
+};
+
+use crate::card::{subscribe_card_events, CardEventKind};
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_QUERY_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    Read,
+    Inserted,
+    Removed,
+    /// APDUs sent to a card through the passthrough.
+    Apdu,
+}
+
+impl From<CardEventKind> for AuditEventKind {
+    #[inline]
+    fn from(kind: CardEventKind) -> Self {
+        match kind {
+            CardEventKind::Inserted => Self::Inserted,
+            CardEventKind::Removed => Self::Removed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time:         DateTime<Local>,
+    pub event:        AuditEventKind,
+    pub reader_name:  Option<String>,
+    /// `None` for the APDU passthrough, whose card is not read.
+    pub card_no_hash: Option<String>,
+    pub client_ip:    Option<IpAddr>,
+    pub api_key_hash: Option<String>,
+    pub endpoint:     Option<String>,
+    /// The `CLA:INS` of each APDU sent through the passthrough. The rest of the APDUs are not recorded since they may contain PINs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apdus:        Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditClient {
+    pub client_ip: Option<IpAddr>,
+    pub api_key:   Option<String>,
+    pub endpoint:  Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    /// Unix timestamp in milliseconds.
+    pub from:   Option<i64>,
+    /// Unix timestamp in milliseconds.
+    pub to:     Option<i64>,
+    pub event:  Option<AuditEventKind>,
+    pub reader: Option<String>,
+    pub limit:  Option<usize>,
+}
+
+impl AuditQuery {
+    #[inline]
+    pub(crate) fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_QUERY_LIMIT)
+    }
+
+    pub(crate) fn matches(&self, record: &AuditRecord) -> bool {
+        let timestamp = record.time.timestamp_millis();
+
+        self.from.map_or(true, |from| timestamp >= from)
+            && self.to.map_or(true, |to| timestamp <= to)
+            && self.event.map_or(true, |event| record.event == event)
+            && self
+                .reader
+                .as_ref()
+                .map_or(true, |reader| record.reader_name.as_ref() == Some(reader))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AuditLogFormat {
+    JsonLines,
+    #[cfg(feature = "audit-sqlite")]
+    Sqlite,
+}
+
+#[derive(Debug)]
+enum AuditStore {
+    JsonLines(json_lines::JsonLinesStore),
+    #[cfg(feature = "audit-sqlite")]
+    Sqlite(sqlite::SqliteStore),
+}
+
+impl AuditStore {
+    fn append(&mut self, record: &AuditRecord) -> anyhow::Result<()> {
+        match self {
+            Self::JsonLines(store) => store.append(record),
+            #[cfg(feature = "audit-sqlite")]
+            Self::Sqlite(store) => store.append(record),
+        }
+    }
+
+    fn query(&mut self, query: &AuditQuery) -> anyhow::Result<Vec<AuditRecord>> {
+        match self {
+            Self::JsonLines(store) => store.query(query),
+            #[cfg(feature = "audit-sqlite")]
+            Self::Sqlite(store) => store.query(query),
+        }
+    }
+
+    fn prune(&mut self, before: DateTime<Local>) -> anyhow::Result<usize> {
+        match self {
+            Self::JsonLines(store) => store.prune(before),
+            #[cfg(feature = "audit-sqlite")]
+            Self::Sqlite(store) => store.prune(before),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AuditLog {
+    store:     Arc<Mutex<AuditStore>>,
+    /// Records are appended by a single writer thread in the order they are sent.
+    sender:    mpsc::UnboundedSender<AuditRecord>,
+    hash_key:  Vec<u8>,
+    retention: Option<chrono::Duration>,
+}
+
+/// Hashes with a secret key, so that the card numbers cannot be recovered by hashing every possible card number.
+#[inline]
+pub fn hash_hex<S: AsRef<[u8]>>(key: &[u8], s: S) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(s.as_ref());
+
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+impl AuditLog {
+    pub fn open(
+        path: PathBuf,
+        format: AuditLogFormat,
+        hash_key: String,
+        retention_days: Option<u32>,
+    ) -> anyhow::Result<Self> {
+        let store = match format {
+            AuditLogFormat::JsonLines => {
+                AuditStore::JsonLines(json_lines::JsonLinesStore::open(path)?)
+            },
+            #[cfg(feature = "audit-sqlite")]
+            AuditLogFormat::Sqlite => AuditStore::Sqlite(sqlite::SqliteStore::open(path)?),
+        };
+
+        let store = Arc::new(Mutex::new(store));
+
+        let (sender, mut receiver) = mpsc::unbounded_channel::<AuditRecord>();
+
+        {
+            let store = store.clone();
+
+            thread::Builder::new().name(String::from("audit-writer")).spawn(move || {
+                while let Some(record) = receiver.blocking_recv() {
+                    if let Err(error) = store.lock().unwrap().append(&record) {
+                        tracing::error!(target: "audit", ?error);
+                    }
+                }
+            })?;
+        }
+
+        Ok(Self {
+            store,
+            sender,
+            hash_key: hash_key.into_bytes(),
+            retention: retention_days.map(|days| chrono::Duration::days(days as i64)),
+        })
+    }
+
+    /// Appends a record in the background.
+    pub fn record(
+        &self,
+        event: AuditEventKind,
+        reader_name: Option<String>,
+        card_no: &str,
+        client: &AuditClient,
+    ) {
+        self.append(event, reader_name, Some(card_no), None, client);
+    }
+
+    /// Appends a record of the APDUs sent through the passthrough in the background.
+    pub fn record_apdus(&self, reader_name: String, apdus: &[Vec<u8>], client: &AuditClient) {
+        let apdus =
+            apdus.iter().map(|apdu| format!("{:02X}:{:02X}", apdu[0], apdu[1])).collect::<Vec<_>>();
+
+        self.append(AuditEventKind::Apdu, Some(reader_name), None, Some(apdus), client);
+    }
+
+    fn append(
+        &self,
+        event: AuditEventKind,
+        reader_name: Option<String>,
+        card_no: Option<&str>,
+        apdus: Option<Vec<String>>,
+        client: &AuditClient,
+    ) {
+        let record = AuditRecord {
+            time: Local::now(),
+            event,
+            reader_name,
+            card_no_hash: card_no.map(|card_no| hash_hex(&self.hash_key, card_no)),
+            client_ip: client.client_ip,
+            api_key_hash: client.api_key.as_ref().map(|api_key| hash_hex(&self.hash_key, api_key)),
+            endpoint: client.endpoint.clone(),
+            apdus,
+        };
+
+        // the writer thread only stops when the log is dropped
+        let _ = self.sender.send(record);
+    }
+
+    pub async fn query(&self, query: AuditQuery) -> anyhow::Result<Vec<AuditRecord>> {
+        let store = self.store.clone();
+
+        task::spawn_blocking(move || store.lock().unwrap().query(&query)).await?
+    }
+
+    async fn prune(&self) -> anyhow::Result<usize> {
+        let Some(retention) = self.retention else {
+            return Ok(0);
+        };
+
+        let before = Local::now() - retention;
+        let store = self.store.clone();
+
+        task::spawn_blocking(move || store.lock().unwrap().prune(before)).await?
+    }
+
+    /// Records card insertions and removals, and removes expired records periodically.
+    pub fn spawn_background_tasks(self: &Arc<Self>) {
+        let audit_log = self.clone();
+
+        task::spawn(async move {
+            let mut receiver = subscribe_card_events();
+
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => audit_log.record(
+                        event.kind.into(),
+                        Some(event.reader_name),
+                        &event.card.card_no,
+                        &AuditClient::default(),
+                    ),
+                    Err(RecvError::Lagged(count)) => {
+                        tracing::warn!(target: "audit", count, "card events lagged");
+                    },
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        if self.retention.is_some() {
+            let audit_log = self.clone();
+
+            task::spawn(async move {
+                loop {
+                    match audit_log.prune().await {
+                        Ok(0) => (),
+                        Ok(count) => {
+                            tracing::info!(target: "audit", count, "expired records removed")
+                        },
+                        Err(error) => tracing::error!(target: "audit", ?error),
+                    }
+
+                    time::sleep(PRUNE_INTERVAL).await;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("audit-test-{}-{name}", std::process::id()))
+    }
+
+    fn record(time: DateTime<Local>, event: AuditEventKind, reader_name: &str) -> AuditRecord {
+        AuditRecord {
+            time,
+            event,
+            reader_name: Some(String::from(reader_name)),
+            card_no_hash: Some(hash_hex(b"key", "000012345678")),
+            client_ip: None,
+            api_key_hash: None,
+            endpoint: None,
+            apdus: None,
+        }
+    }
+
+    fn round_trip(mut store: AuditStore) {
+        let now = Local::now();
+        let old = now - chrono::Duration::days(10);
+
+        let records = [
+            record(old, AuditEventKind::Inserted, "Reader 1"),
+            record(old, AuditEventKind::Read, "Reader 1"),
+            record(now, AuditEventKind::Read, "Reader 2"),
+            record(now, AuditEventKind::Removed, "Reader 1"),
+        ];
+
+        for record in records.iter() {
+            store.append(record).unwrap();
+        }
+
+        let query = |store: &mut AuditStore, query: AuditQuery| {
+            store
+                .query(&query)
+                .unwrap()
+                .into_iter()
+                .map(|record| (record.event, record.reader_name.unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(4, query(&mut store, AuditQuery::default()).len());
+
+        assert_eq!(
+            vec![
+                (AuditEventKind::Read, String::from("Reader 1")),
+                (AuditEventKind::Read, String::from("Reader 2"))
+            ],
+            query(&mut store, AuditQuery {
+                event: Some(AuditEventKind::Read),
+                ..AuditQuery::default()
+            })
+        );
+
+        assert_eq!(
+            vec![(AuditEventKind::Removed, String::from("Reader 1"))],
+            query(&mut store, AuditQuery {
+                from: Some((now - chrono::Duration::days(1)).timestamp_millis()),
+                reader: Some(String::from("Reader 1")),
+                ..AuditQuery::default()
+            })
+        );
+
+        // the latest records are kept in order
+        assert_eq!(
+            vec![
+                (AuditEventKind::Read, String::from("Reader 2")),
+                (AuditEventKind::Removed, String::from("Reader 1"))
+            ],
+            query(&mut store, AuditQuery {
+                limit: Some(2),
+                ..AuditQuery::default()
+            })
+        );
+
+        assert_eq!(2, store.prune(now - chrono::Duration::days(1)).unwrap());
+        assert_eq!(0, store.prune(now - chrono::Duration::days(1)).unwrap());
+
+        store.append(&record(now, AuditEventKind::Inserted, "Reader 2")).unwrap();
+
+        assert_eq!(
+            vec![
+                (AuditEventKind::Read, String::from("Reader 2")),
+                (AuditEventKind::Removed, String::from("Reader 1")),
+                (AuditEventKind::Inserted, String::from("Reader 2"))
+            ],
+            query(&mut store, AuditQuery::default())
+        );
+    }
+
+    #[test]
+    fn json_lines_store() {
+        let path = temp_path("audit.jsonl");
+
+        let _ = std::fs::remove_file(&path);
+
+        round_trip(AuditStore::JsonLines(json_lines::JsonLinesStore::open(path.clone()).unwrap()));
+
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+
+        assert!(!PathBuf::from(temp_path).exists());
+        assert_eq!(3, std::fs::read_to_string(&path).unwrap().lines().count());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "audit-sqlite")]
+    #[test]
+    fn sqlite_store() {
+        let path = temp_path("audit.sqlite");
+
+        let _ = std::fs::remove_file(&path);
+
+        round_trip(AuditStore::Sqlite(sqlite::SqliteStore::open(path.clone()).unwrap()));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn hmac() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            hash_hex(b"Jefe", "what do ya want for nothing?")
+        );
+
+        assert_ne!(hash_hex(b"key 1", "000012345678"), hash_hex(b"key 2", "000012345678"));
+    }
+}
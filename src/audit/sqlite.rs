@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use chrono::prelude::*;
+use rusqlite::{params, Connection};
+
+use super::{AuditQuery, AuditRecord};
+
+#[derive(Debug)]
+pub(super) struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    pub(super) fn open(path: PathBuf) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                record TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS audit_timestamp ON audit (timestamp);",
+        )?;
+
+        Ok(Self {
+            connection,
+        })
+    }
+
+    pub(super) fn append(&mut self, record: &AuditRecord) -> anyhow::Result<()> {
+        self.connection.execute(
+            "INSERT INTO audit (timestamp, record) VALUES (?1, ?2)",
+            params![record.time.timestamp_millis(), serde_json::to_string(record)?],
+        )?;
+
+        Ok(())
+    }
+
+    pub(super) fn query(&mut self, query: &AuditQuery) -> anyhow::Result<Vec<AuditRecord>> {
+        let mut statement = self.connection.prepare(
+            "SELECT record FROM audit WHERE timestamp >= ?1 AND timestamp <= ?2 ORDER BY id DESC",
+        )?;
+
+        let rows = statement.query_map(
+            params![query.from.unwrap_or(i64::MIN), query.to.unwrap_or(i64::MAX)],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        let limit = query.limit();
+
+        let mut records = Vec::new();
+
+        for row in rows {
+            if records.len() >= limit {
+                break;
+            }
+
+            let record: AuditRecord = serde_json::from_str(&row?)?;
+
+            if query.matches(&record) {
+                records.push(record);
+            }
+        }
+
+        records.reverse();
+
+        Ok(records)
+    }
+
+    pub(super) fn prune(&mut self, before: DateTime<Local>) -> anyhow::Result<usize> {
+        Ok(self.connection.execute("DELETE FROM audit WHERE timestamp < ?1", params![
+            before.timestamp_millis()
+        ])?)
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Replaces a character which cannot be mapped to Unicode.
+pub const PLACEHOLDER: char = '\u{FFFD}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Big5ExtensionsParseError {
+    pub line: usize,
+}
+
+impl Display for Big5ExtensionsParseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("Big5 擴充字對應表第 {} 行的格式不正確", self.line))
+    }
+}
+
+impl Error for Big5ExtensionsParseError {}
+
+/// Maps a code in the user-defined areas of Big5 to the private use area of Unicode in the same way as Windows (CP950). Government systems put the rare characters of names in these areas, and a computer with the matching EUDC font installed shows them correctly.
+///
+/// The areas are `FA40`–`FEFE` (`U+E000`–`U+E310`), `8E40`–`A0FE` (`U+E311`–`U+EEB7`), `8140`–`8DFE` (`U+EEB8`–`U+F6B0`) and `C6A1`–`C8FE` (`U+F6B1`–`U+F848`), with 157 characters in each row.
+pub fn eudc_to_unicode(code: u16) -> Option<char> {
+    let [lead, trail] = code.to_be_bytes();
+
+    let column = match trail {
+        0x40..=0x7E => trail - 0x40,
+        0xA1..=0xFE => trail - 0xA1 + 63,
+        _ => return None,
+    } as u32;
+
+    let (first_lead, base) = match lead {
+        0xFA..=0xFE => (0xFA, 0xE000),
+        0x8E..=0xA0 => (0x8E, 0xE311),
+        0x81..=0x8D => (0x81, 0xEEB8),
+        // the area starts in the middle of the row of `C6`
+        0xC6 if trail >= 0xA1 => (0xC6, 0xF6B1 - 63),
+        0xC7..=0xC8 => (0xC6, 0xF6B1 - 63),
+        _ => return None,
+    };
+
+    char::from_u32(base + (lead - first_lead) as u32 * 157 + column)
+}
+
+/// Maps Big5 code points, e.g. the ones in the user-defined areas used by government systems, to Unicode. The mapping overrides the built-in mapping of the user-defined areas (see `eudc_to_unicode`) and Big5-HKSCS.
+///
+/// A table which maps the rare characters to their standard Unicode code points can be converted from the CNS 11643 (全字庫) mapping tables and loaded with `parse`.
+#[derive(Debug, Default, Clone)]
+pub struct Big5Extensions {
+    map: HashMap<u16, char>,
+}
+
+impl Big5Extensions {
+    /// Parses a mapping table. Each line has a Big5 code and a Unicode code point in hex, separated by whitespace, e.g. `FA40 U+2420E`, which can be converted from the CNS 11643 (全字庫) mapping tables. Empty lines and the text after `#` are ignored.
+    pub fn parse(s: &str) -> Result<Self, Big5ExtensionsParseError> {
+        let mut map = HashMap::new();
+
+        for (index, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = Big5ExtensionsParseError {
+                line: index + 1
+            };
+
+            let mut tokens = line.split_whitespace();
+
+            let (Some(big5), Some(unicode), None) = (tokens.next(), tokens.next(), tokens.next())
+            else {
+                return Err(error);
+            };
+
+            let big5 = u16::from_str_radix(big5.trim_start_matches("0x"), 16).map_err(|_| error)?;
+
+            let unicode = unicode
+                .strip_prefix("U+")
+                .or_else(|| unicode.strip_prefix("u+"))
+                .unwrap_or(unicode);
+
+            let unicode =
+                u32::from_str_radix(unicode, 16).ok().and_then(char::from_u32).ok_or(error)?;
+
+            map.insert(big5, unicode);
+        }
+
+        Ok(Self {
+            map,
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, code: u16) -> Option<char> {
+        self.map.get(&code).copied()
+    }
+}
+
+/// How to decode the Big5 name on a card.
+#[derive(Debug, Default, Clone)]
+pub struct NameDecoding {
+    /// Substitutes unmapped characters with the placeholder instead of rejecting the card.
+    pub lenient:    bool,
+    pub extensions: Big5Extensions,
+}
+
+#[inline]
+fn is_trail_byte(b: u8) -> bool {
+    matches!(b, 0x40..=0x7E | 0xA1..=0xFE)
+}
+
+impl NameDecoding {
+    /// Decodes the name character by character. Returns `None` if there are unmapped characters in the strict mode, otherwise the name and whether there are unmapped characters.
+    pub fn decode(&self, data: &[u8]) -> Option<(String, bool)> {
+        let mut name = String::with_capacity(data.len() * 3 / 2);
+        let mut has_unmapped_chars = false;
+
+        let mut i = 0;
+
+        while i < data.len() {
+            let b = data[i];
+
+            if b < 0x80 {
+                name.push(b as char);
+
+                i += 1;
+
+                continue;
+            }
+
+            if (0x81..=0xFE).contains(&b) && i + 1 < data.len() && is_trail_byte(data[i + 1]) {
+                let code = u16::from_be_bytes([b, data[i + 1]]);
+
+                // the mappings take precedence since the user-defined areas overlap with HKSCS
+                if let Some(c) = self.extensions.get(code).or_else(|| eudc_to_unicode(code)) {
+                    name.push(c);
+                } else {
+                    let (s, had_errors) =
+                        encoding_rs::BIG5.decode_without_bom_handling(&data[i..i + 2]);
+
+                    if had_errors {
+                        name.push(PLACEHOLDER);
+
+                        has_unmapped_chars = true;
+                    } else {
+                        name.push_str(&s);
+                    }
+                }
+
+                i += 2;
+            } else {
+                name.push(PLACEHOLDER);
+
+                has_unmapped_chars = true;
+
+                i += 1;
+            }
+        }
+
+        if has_unmapped_chars && !self.lenient {
+            None
+        } else {
+            Some((name, has_unmapped_chars))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eudc() {
+        assert_eq!(Some('\u{E000}'), eudc_to_unicode(0xFA40));
+        assert_eq!(Some('\u{E03F}'), eudc_to_unicode(0xFAA1));
+        assert_eq!(Some('\u{E310}'), eudc_to_unicode(0xFEFE));
+        assert_eq!(Some('\u{E311}'), eudc_to_unicode(0x8E40));
+        assert_eq!(Some('\u{EEB7}'), eudc_to_unicode(0xA0FE));
+        assert_eq!(Some('\u{EEB8}'), eudc_to_unicode(0x8140));
+        assert_eq!(Some('\u{F6B0}'), eudc_to_unicode(0x8DFE));
+        assert_eq!(Some('\u{F6B1}'), eudc_to_unicode(0xC6A1));
+        assert_eq!(Some('\u{F70F}'), eudc_to_unicode(0xC740));
+        assert_eq!(Some('\u{F848}'), eudc_to_unicode(0xC8FE));
+
+        assert_eq!(None, eudc_to_unicode(0xC67E));
+        assert_eq!(None, eudc_to_unicode(0xA440));
+        assert_eq!(None, eudc_to_unicode(0xFA80));
+    }
+
+    #[test]
+    fn decode() {
+        let decoding = NameDecoding::default();
+
+        assert_eq!(
+            Some((String::from("王\u{E000}明"), false)),
+            decoding.decode(b"\xA4\xFD\xFA\x40\xA9\xFA")
+        );
+
+        // a trailing lead byte cannot be decoded
+        assert_eq!(None, decoding.decode(b"\xA4\xFD\xA4"));
+
+        let decoding = NameDecoding {
+            lenient:    true,
+            extensions: Big5Extensions::parse("FA40 U+5553 # 啓").unwrap(),
+        };
+
+        assert_eq!(
+            Some((String::from("王啓\u{FFFD}"), true)),
+            decoding.decode(b"\xA4\xFD\xFA\x40\xA4")
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::HashMap, ffi::CString, sync::Mutex, thread, time::Duration};
+
+use pcsc::{
+    Card, Context, Disposition, Protocols, ReaderState, Scope, ShareMode, State, Transaction,
+};
+
+/// The source of readers and cards. The default one is PC/SC, and the others are used for recording and replaying APDU sessions. Readers are read in parallel, so a backend is shared between threads.
+pub trait CardBackend: Send + Sync {
+    /// Recovers from an error, e.g. re-establishes the PC/SC context.
+    fn reestablish(&self) -> Result<(), pcsc::Error>;
+
+    fn list_readers(&self) -> Result<Vec<String>, pcsc::Error>;
+
+    fn connect(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error>;
+
+    /// Connects to the card in a reader without recording the APDUs, which is used for the passthrough since its APDUs may contain a PIN.
+    #[inline]
+    fn connect_unrecorded(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        self.connect(reader)
+    }
+
+    /// Returns the counter of card insertions and removals of a reader if the backend supports it.
+    #[inline]
+    fn card_event_count(&self, _reader: &str) -> Option<u32> {
+        None
+    }
+
+    /// Returns whether another application is connected to the card in a reader.
+    #[inline]
+    fn card_in_use(&self, _reader: &str) -> bool {
+        false
+    }
+
+    /// Blocks until a reader or a card changes, or until the timeout. The default one cannot be notified, so it just waits for the timeout.
+    #[inline]
+    fn wait_for_change(&self, timeout: Duration) -> Result<(), pcsc::Error> {
+        thread::sleep(timeout);
+
+        Ok(())
+    }
+}
+
+pub trait CardConnection: Send {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error>;
+
+    /// Calls `f` in an exclusive transaction, so that other applications cannot access the card in between the APDUs.
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error>;
+}
+
+/// Lists readers with one context, connects to each reader with its own context, and waits for changes with another one, since a context should not be used by multiple threads at the same time.
+#[derive(Default)]
+pub struct PcscBackend {
+    context:         Mutex<Option<Context>>,
+    readers:         Mutex<Vec<(String, CString)>>,
+    reader_contexts: Mutex<HashMap<String, Context>>,
+    watch_context:   Mutex<Option<Context>>,
+    /// The last known states of the readers and of the plug-and-play notification, so that a change happening between two waits is not missed.
+    watched_states:  Mutex<Vec<ReaderState>>,
+}
+
+impl PcscBackend {
+    fn reader_context(&self, reader: &str) -> Result<(Context, CString), pcsc::Error> {
+        let reader_cs = match self.readers.lock().unwrap().iter().find(|(name, _)| name == reader) {
+            Some((_, reader_cs)) => reader_cs.clone(),
+            None => CString::new(reader).map_err(|_| pcsc::Error::UnknownReader)?,
+        };
+
+        let mut reader_contexts = self.reader_contexts.lock().unwrap();
+
+        let context = match reader_contexts.get(reader) {
+            Some(context) => context.clone(),
+            None => {
+                let context = Context::establish(Scope::User)?;
+
+                reader_contexts.insert(String::from(reader), context.clone());
+
+                context
+            },
+        };
+
+        Ok((context, reader_cs))
+    }
+
+    fn reader_state(&self, reader: &str) -> Option<ReaderState> {
+        let (context, reader_cs) = self.reader_context(reader).ok()?;
+
+        let mut reader_states = [ReaderState::new(reader_cs, State::UNAWARE)];
+
+        context.get_status_change(Duration::ZERO, &mut reader_states).ok()?;
+
+        let [reader_state] = reader_states;
+
+        Some(reader_state)
+    }
+}
+
+impl CardBackend for PcscBackend {
+    fn reestablish(&self) -> Result<(), pcsc::Error> {
+        let context = Context::establish(Scope::User)?;
+
+        *self.context.lock().unwrap() = Some(context);
+
+        self.reader_contexts.lock().unwrap().clear();
+
+        Ok(())
+    }
+
+    fn list_readers(&self) -> Result<Vec<String>, pcsc::Error> {
+        let context = self.context.lock().unwrap().clone();
+
+        let context = match context {
+            Some(context) => context,
+            None => {
+                self.reestablish()?;
+
+                self.context.lock().unwrap().clone().unwrap()
+            },
+        };
+
+        let list = || -> Result<Vec<(String, CString)>, pcsc::Error> {
+            let size = context.list_readers_len()?.max(4096);
+
+            let mut buffer: Vec<u8> = vec![0u8; size];
+
+            Ok(context
+                .list_readers(&mut buffer)?
+                .map(|name| (name.to_string_lossy().into_owned(), name.to_owned()))
+                .collect())
+        };
+
+        // no readers is not an error, so that the readers which have been unplugged are forgotten
+        let readers = match list() {
+            Ok(readers) => readers,
+            Err(pcsc::Error::NoReadersAvailable) => Vec::new(),
+            Err(error) => return Err(error),
+        };
+
+        let names = readers.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+
+        self.reader_contexts.lock().unwrap().retain(|reader, _| names.contains(reader));
+
+        *self.readers.lock().unwrap() = readers;
+
+        Ok(names)
+    }
+
+    fn connect(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        let (context, reader_cs) = self.reader_context(reader)?;
+
+        let card = match context.connect(&reader_cs, ShareMode::Shared, Protocols::ANY) {
+            Ok(card) => card,
+            Err(error) => {
+                if matches!(
+                    error,
+                    pcsc::Error::InvalidHandle
+                        | pcsc::Error::NoService
+                        | pcsc::Error::ServiceStopped
+                ) {
+                    self.reader_contexts.lock().unwrap().remove(reader);
+                }
+
+                return Err(error);
+            },
+        };
+
+        Ok(Box::new(PcscConnection {
+            card,
+        }))
+    }
+
+    #[inline]
+    fn card_event_count(&self, reader: &str) -> Option<u32> {
+        self.reader_state(reader).map(|reader_state| reader_state.event_count())
+    }
+
+    #[inline]
+    fn card_in_use(&self, reader: &str) -> bool {
+        self.reader_state(reader).is_some_and(|reader_state| {
+            reader_state.event_state().intersects(State::INUSE | State::EXCLUSIVE)
+        })
+    }
+
+    fn wait_for_change(&self, timeout: Duration) -> Result<(), pcsc::Error> {
+        let mut watched_states = self.watched_states.lock().unwrap();
+
+        let context = {
+            let mut watch_context = self.watch_context.lock().unwrap();
+
+            match watch_context.as_ref() {
+                Some(context) => context.clone(),
+                None => watch_context.insert(Context::establish(Scope::User)?).clone(),
+            }
+        };
+
+        let pnp_notification = pcsc::PNP_NOTIFICATION();
+
+        // readers which are no longer listed are dropped, and new ones start as unaware so that they are reported at once
+        let mut reader_states = self
+            .readers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, reader_cs)| reader_cs.as_c_str())
+            .chain([pnp_notification])
+            .map(|name| {
+                match watched_states.iter().position(|reader_state| reader_state.name() == name) {
+                    Some(index) => watched_states.swap_remove(index),
+                    None => ReaderState::new(name, State::UNAWARE),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let result = match context.get_status_change(timeout, &mut reader_states) {
+            Ok(()) => {
+                for reader_state in reader_states.iter_mut() {
+                    reader_state.sync_current_state();
+                }
+
+                Ok(())
+            },
+            Err(pcsc::Error::Timeout) => Ok(()),
+            Err(error) => {
+                if matches!(
+                    error,
+                    pcsc::Error::InvalidHandle
+                        | pcsc::Error::NoService
+                        | pcsc::Error::ServiceStopped
+                ) {
+                    *self.watch_context.lock().unwrap() = None;
+                }
+
+                Err(error)
+            },
+        };
+
+        *watched_states = reader_states;
+
+        result
+    }
+}
+
+pub struct PcscConnection {
+    card: Card,
+}
+
+impl CardConnection for PcscConnection {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        let mut buffer = [0u8; pcsc::MAX_BUFFER_SIZE];
+
+        Ok(self.card.transmit(apdu, &mut buffer)?.to_vec())
+    }
+
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        let mut connection = PcscTransaction {
+            transaction: self.card.transaction()?
+        };
+
+        f(&mut connection);
+
+        // the card has been read, so failing to end the transaction, e.g. the card is removed right after, is not a failure of the read
+        if let Err((_, error)) = connection.transaction.end(Disposition::LeaveCard) {
+            tracing::warn!(target: "card", ?error, "cannot end the transaction");
+        }
+
+        Ok(())
+    }
+}
+
+struct PcscTransaction<'a> {
+    transaction: Transaction<'a>,
+}
+
+impl CardConnection for PcscTransaction<'_> {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        let mut buffer = [0u8; pcsc::MAX_BUFFER_SIZE];
+
+        Ok(self.transaction.transmit(apdu, &mut buffer)?.to_vec())
+    }
+
+    /// Already in a transaction.
+    #[inline]
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        f(self);
+
+        Ok(())
+    }
+}
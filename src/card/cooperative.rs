@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How long a reader can be left alone in a row. Some applications keep the card connected, so the reader is tried once in a while anyway.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// The quiet period, which is set if the cooperative mode is enabled.
+static QUIET_PERIOD: OnceCell<Duration> = OnceCell::new();
+static BUSY_READERS: Lazy<Mutex<HashMap<String, BusyReader>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug)]
+struct BusyReader {
+    until:   Instant,
+    backoff: Duration,
+    /// When the reader was found busy first, or was tried last because of `MAX_WAIT`.
+    since:   Instant,
+}
+
+/// Enables the cooperative mode. Cards are read in transactions, and a reader is left alone for `quiet_period` after another application has accessed its card. This should be called before any card is read.
+pub fn set_cooperative(quiet_period: Duration) {
+    if QUIET_PERIOD.set(quiet_period).is_err() {
+        panic!("the cooperative mode has been set");
+    }
+}
+
+#[inline]
+pub(super) fn is_enabled() -> bool {
+    QUIET_PERIOD.get().is_some()
+}
+
+/// Whether the error means that another application is holding the reader.
+#[inline]
+pub(super) fn is_busy_error(error: pcsc::Error) -> bool {
+    matches!(error, pcsc::Error::SharingViolation | pcsc::Error::ReaderUnavailable)
+}
+
+/// Whether the reader should be left alone. A reader which has been busy for `MAX_WAIT` is not busy once, so that it is tried again.
+pub(super) fn is_busy(reader: &str) -> bool {
+    let mut busy_readers = BUSY_READERS.lock().unwrap();
+
+    let Some(busy) = busy_readers.get_mut(reader) else {
+        return false;
+    };
+
+    let now = Instant::now();
+
+    if busy.until <= now {
+        return false;
+    }
+
+    if now.duration_since(busy.since) >= MAX_WAIT {
+        tracing::debug!(target: "card", reader, "the reader has been busy for too long, try anyway");
+
+        busy.since = now;
+
+        return false;
+    }
+
+    true
+}
+
+/// Another application is using the card, so wait for the quiet period after it is done.
+pub(super) fn mark_in_use(reader: &str) {
+    let quiet_period = QUIET_PERIOD.get().copied().unwrap_or_default();
+
+    let mut busy_readers = BUSY_READERS.lock().unwrap();
+
+    let now = Instant::now();
+    let until = now + quiet_period;
+
+    match busy_readers.get_mut(reader) {
+        Some(busy) => busy.until = busy.until.max(until),
+        None => {
+            busy_readers.insert(String::from(reader), BusyReader {
+                until,
+                backoff: Duration::ZERO,
+                since: now,
+            });
+        },
+    }
+}
+
+/// The reader was busy when it was accessed, so wait longer each time until a read succeeds.
+pub(super) fn back_off(reader: &str) {
+    let quiet_period = QUIET_PERIOD.get().copied().unwrap_or_default();
+
+    let mut busy_readers = BUSY_READERS.lock().unwrap();
+
+    let now = Instant::now();
+
+    let busy = busy_readers.entry(String::from(reader)).or_insert(BusyReader {
+        until:   now,
+        backoff: Duration::ZERO,
+        since:   now,
+    });
+
+    busy.backoff = (busy.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+    busy.until = now + busy.backoff.max(quiet_period);
+
+    tracing::debug!(target: "card", reader, backoff = ?busy.backoff, "the reader is busy");
+}
+
+/// The reader has been read successfully.
+#[inline]
+pub(super) fn clear(reader: &str) {
+    BUSY_READERS.lock().unwrap().remove(reader);
+}
+
+/// Forgets the readers which are gone.
+#[inline]
+pub(super) fn retain(readers: &[String]) {
+    BUSY_READERS.lock().unwrap().retain(|reader, _| readers.contains(reader));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_wait() {
+        let reader = "Shared Reader";
+        let now = Instant::now();
+
+        let Some(since) = now.checked_sub(MAX_WAIT + Duration::from_secs(1)) else {
+            return;
+        };
+
+        BUSY_READERS.lock().unwrap().insert(String::from(reader), BusyReader {
+            until: now + MAX_WAIT,
+            backoff: MAX_BACKOFF,
+            since,
+        });
+
+        // tried once, and then left alone again
+        assert!(!is_busy(reader));
+        assert!(is_busy(reader));
+
+        mark_in_use(reader);
+
+        assert!(is_busy(reader));
+
+        clear(reader);
+
+        assert!(!is_busy(reader));
+    }
+}
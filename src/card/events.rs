@@ -0,0 +1,79 @@
+use std::sync::Mutex;
+
+use chrono::prelude::*;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::NHICardBasic;
+
+static CARD_EVENTS: Lazy<broadcast::Sender<CardEvent>> = Lazy::new(|| broadcast::channel(64).0);
+static PREVIOUS_CARDS: Lazy<Mutex<Vec<NHICardBasic>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardEventKind {
+    Inserted,
+    Removed,
+}
+
+/// The insertion or removal of an NHI card. The cards of the other types do not emit events.
+#[derive(Debug, Clone, Serialize)]
+pub struct CardEvent {
+    pub kind:        CardEventKind,
+    pub time:        DateTime<Local>,
+    pub reader_name: String,
+    pub card:        NHICardBasic,
+}
+
+#[inline]
+pub fn subscribe_card_events() -> broadcast::Receiver<CardEvent> {
+    CARD_EVENTS.subscribe()
+}
+
+#[inline]
+fn is_same_card(a: &NHICardBasic, b: &NHICardBasic) -> bool {
+    a.reader_name == b.reader_name && a.card_no == b.card_no && a.session_id == b.session_id
+}
+
+/// Compares the cards with the ones from the previous successful read and broadcasts the differences. The cards in `unchanged_readers`, e.g. the readers which timed out, are assumed to be unchanged.
+pub(super) fn detect_changes(cards: &[NHICardBasic], unchanged_readers: &[String]) {
+    let mut previous_cards = PREVIOUS_CARDS.lock().unwrap();
+
+    let time = Local::now();
+
+    let is_unchanged = |card: &NHICardBasic| {
+        card.reader_name.as_ref().is_some_and(|reader| unchanged_readers.contains(reader))
+    };
+
+    for card in previous_cards.iter() {
+        if !is_unchanged(card) && !cards.iter().any(|c| is_same_card(c, card)) {
+            emit(CardEventKind::Removed, time, card);
+        }
+    }
+
+    for card in cards {
+        if !previous_cards.iter().any(|c| is_same_card(c, card)) {
+            emit(CardEventKind::Inserted, time, card);
+        }
+    }
+
+    let kept_cards =
+        previous_cards.iter().filter(|card| is_unchanged(card)).cloned().collect::<Vec<_>>();
+
+    *previous_cards = cards.iter().cloned().chain(kept_cards).collect();
+}
+
+fn emit(kind: CardEventKind, time: DateTime<Local>, card: &NHICardBasic) {
+    let reader_name = card.reader_name.clone().unwrap_or_default();
+
+    tracing::debug!(target: "card", reader = reader_name, ?kind);
+
+    // no receivers is not an error
+    let _ = CARD_EVENTS.send(CardEvent {
+        kind,
+        time,
+        reader_name,
+        card: card.clone(),
+    });
+}
@@ -1,163 +1,555 @@
-mod nhi_card_basic;
+mod backend;
+mod cooperative;
+mod events;
+mod passthrough;
+mod poller;
+mod profile;
+mod recorder;
+mod replay;
+mod session;
+mod simulator;
+mod status;
+
+use std::{
+    collections::HashSet,
+    marker::PhantomData,
+    ptr::{addr_of, addr_of_mut},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+pub use backend::*;
+pub use cooperative::set_cooperative;
+pub use events::*;
+use futures::future;
+use once_cell::sync::{Lazy, OnceCell};
+pub use passthrough::{parse_apdus, transmit_apdus, ApduPattern, PassthroughError};
+pub use poller::spawn_card_poller;
+use profile::{CardProfile, ProfileError, APDU_READ, APDU_SELECT, PROFILES};
+pub use recorder::RecordingBackend;
+pub use replay::ReplayBackend;
+pub use simulator::{SimulatedCard, Simulator, SimulatorError};
+pub use status::*;
+use tokio::{sync::Mutex, task, time};
+pub use tw_nhi_icc_service::*;
+
+use crate::metrics::{self, CardReadResult};
+
+static BACKEND: OnceCell<Arc<dyn CardBackend>> = OnceCell::new();
+static mut CARD_LIST: Vec<CardRecord> = Vec::new();
+static NAME_DECODING: OnceCell<NameDecoding> = OnceCell::new();
+static DERIVED_FIELDS: AtomicBool = AtomicBool::new(false);
+static READER_TIMEOUT_MILLIS: AtomicU64 = AtomicU64::new(10_000);
+/// The readers which are being read. A reader whose previous read has not finished is skipped.
+static READING_READERS: Lazy<std::sync::Mutex<HashSet<String>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashSet::new()));
+static LOCK: Lazy<Mutex<PhantomData<bool>>> = Lazy::new(|| Mutex::new(PhantomData));
+static LOCK_GET: Lazy<Mutex<PhantomData<bool>>> = Lazy::new(|| Mutex::new(PhantomData));
 
-use std::marker::PhantomData;
+/// Replaces the default PC/SC backend. This should be called before any card is read.
+pub fn set_backend(backend: Box<dyn CardBackend>) {
+    if BACKEND.set(Arc::from(backend)).is_err() {
+        panic!("the card backend has been set");
+    }
+}
 
-pub use nhi_card_basic::*;
-use once_cell::sync::Lazy;
-use pcsc::{Context, Protocols, Scope, ShareMode};
-use tokio::{sync::Mutex, task};
+#[inline]
+fn backend() -> Arc<dyn CardBackend> {
+    BACKEND.get_or_init(|| Arc::new(PcscBackend::default())).clone()
+}
 
-const APDU_SELECT: &[u8] =
-    b"\x00\xA4\x04\x00\x10\xD1\x58\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x11\x00";
-const APDU_READ: &[u8] = b"\x00\xCA\x11\x00\x02\x00\x00";
+/// Sets how names are decoded. This should be called before any card is read.
+pub fn set_name_decoding(name_decoding: NameDecoding) {
+    if NAME_DECODING.set(name_decoding).is_err() {
+        panic!("the name decoding has been set");
+    }
+}
 
-pub static mut CONTEXT: Option<Context> = None;
-static mut NHI_CARD_LIST: Vec<NHICardBasic> = Vec::new();
-static LOCK: Lazy<Mutex<PhantomData<bool>>> = Lazy::new(|| Mutex::new(PhantomData));
-static LOCK_GET: Lazy<Mutex<PhantomData<bool>>> = Lazy::new(|| Mutex::new(PhantomData));
+/// Sets whether to compute the derived fields, e.g. the age, for every card read.
+#[inline]
+pub fn set_derived_fields(enabled: bool) {
+    DERIVED_FIELDS.store(enabled, Ordering::Relaxed);
+}
 
-unsafe fn update_nhi_cards(retry: bool) -> Result<(), pcsc::Error> {
-    debug_assert!(LOCK.try_lock().is_err());
+/// Sets how long to wait for a reader. A reader which takes longer is reported as timed out and its card is not returned.
+#[inline]
+pub fn set_reader_timeout(timeout: Duration) {
+    READER_TIMEOUT_MILLIS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
 
-    if retry {
-        tracing::info!(target: "card", "try to re-establish card context");
+#[inline]
+fn transmit_timed(
+    card: &mut dyn CardConnection,
+    command: &'static str,
+    apdu: &[u8],
+) -> Result<Vec<u8>, pcsc::Error> {
+    let _timer = metrics::APDU_DURATION.with_label_values(&[command]).start_timer();
+
+    card.transmit(apdu)
+}
 
-        CONTEXT = Some(Context::establish(Scope::User)?);
-    } else {
-        NHI_CARD_LIST.clear();
+fn list_readers(backend: &dyn CardBackend) -> Result<Vec<String>, pcsc::Error> {
+    match backend.list_readers() {
+        Ok(readers) => Ok(readers),
+        Err(_) => {
+            tracing::info!(target: "card", "try to re-establish card context");
+
+            metrics::CONTEXT_REESTABLISHMENTS.inc();
+
+            backend.reestablish()?;
+
+            backend.list_readers()
+        },
+    }
+}
+
+/// The outcome of reading a reader.
+enum ReaderRead {
+    Card(Box<CardRecord>),
+    /// No card.
+    Empty,
+    /// The card is not supported or cannot be parsed, which does not change until the card is removed.
+    Unsupported,
+    /// The card cannot be read this time, e.g. it responds with an error. The card is assumed to be unchanged.
+    Failed,
+    /// Another application is using the reader in the cooperative mode. The card is assumed to be unchanged.
+    Busy,
+    /// The card is assumed to be unchanged.
+    TimedOut,
+}
+
+/// Whether the error means that the card is not in the reader anymore.
+#[inline]
+fn is_removed_error(error: pcsc::Error) -> bool {
+    matches!(error, pcsc::Error::NoSmartcard | pcsc::Error::RemovedCard)
+}
+
+/// Whether another application has taken the card in the middle of a read in the cooperative mode. Otherwise the error is a failure.
+#[inline]
+fn is_busy_error(error: pcsc::Error) -> bool {
+    cooperative::is_enabled() && cooperative::is_busy_error(error)
+}
+
+/// Selects the application of each profile in turn, and reads the card with the first profile whose application is found.
+fn read_card(card: &mut dyn CardConnection, reader: &str) -> ReaderRead {
+    for profile in PROFILES {
+        match transmit_timed(card, "select", profile.select_apdu()) {
+            Ok(response) if profile::is_selected(&response) => {
+                tracing::debug!(target: "card", reader, card_type = profile.card_type(), "selected");
+
+                return read_profile(card, reader, *profile);
+            },
+            Ok(_) => continue,
+            Err(error) if is_removed_error(error) => {
+                metrics::record_card_read(CardReadResult::NoCard);
+
+                return ReaderRead::Empty;
+            },
+            Err(error) if is_busy_error(error) => {
+                tracing::debug!(target: "card", reader, ?error, "the reader is taken");
+
+                return ReaderRead::Busy;
+            },
+            Err(error) => {
+                tracing::warn!(target: "card", reader, ?error);
+
+                metrics::record_card_read(CardReadResult::Error);
+
+                return ReaderRead::Failed;
+            },
+        }
     }
 
-    let context = CONTEXT.as_ref().unwrap();
+    tracing::warn!(target: "card", reader, "unsupported reader");
+
+    metrics::record_card_read(CardReadResult::Unsupported);
 
-    let size = match context.list_readers_len() {
-        Ok(len) => len.max(4096),
+    ReaderRead::Unsupported
+}
+
+fn read_profile(
+    card: &mut dyn CardConnection,
+    reader: &str,
+    profile: &dyn CardProfile,
+) -> ReaderRead {
+    match profile.read(card) {
+        Ok(record) => ReaderRead::Card(Box::new(record)),
+        Err(ProfileError::Pcsc(error)) if is_removed_error(error) => {
+            metrics::record_card_read(CardReadResult::NoCard);
+
+            ReaderRead::Empty
+        },
+        Err(ProfileError::Pcsc(error)) if is_busy_error(error) => {
+            tracing::debug!(target: "card", reader, ?error, "the reader is taken");
+
+            ReaderRead::Busy
+        },
         Err(error) => {
-            if retry {
-                return Err(error);
-            } else {
-                return update_nhi_cards(true);
+            tracing::warn!(target: "card", reader, card_type = profile.card_type(), %error);
+
+            match error {
+                ProfileError::Parse(_) => {
+                    metrics::record_card_read(CardReadResult::ParseError);
+
+                    ReaderRead::Unsupported
+                },
+                _ => {
+                    metrics::record_card_read(CardReadResult::Error);
+
+                    ReaderRead::Failed
+                },
             }
         },
-    };
+    }
+}
 
-    let mut buffer: Vec<u8> = vec![0u8; size];
+/// Computes the derived fields of an NHI card if enabled.
+#[inline]
+fn derive_fields(record: &mut CardRecord) {
+    if let CardRecord::Nhi(basic) = record {
+        if DERIVED_FIELDS.load(Ordering::Relaxed) {
+            basic.derived = Some(basic.derive(taipei_today()));
+        }
+    }
+}
 
-    let names = match context.list_readers(&mut buffer) {
-        Ok(names) => names,
-        Err(error) => {
-            if retry {
-                return Err(error);
-            } else {
-                return update_nhi_cards(true);
-            }
+/// Handles a read which has failed. The card read before is kept unless the event counter of the reader has changed, so that one failed read is not taken for a removal.
+fn read_failed(reader: &str, event_count: Option<u32>) -> ReaderRead {
+    if !session::is_current(reader, event_count) {
+        session::end(reader);
+
+        return ReaderRead::Empty;
+    }
+
+    match session::cached(reader, event_count) {
+        Some(mut record) => {
+            derive_fields(&mut record);
+
+            ReaderRead::Card(Box::new(record))
         },
-    };
+        None => ReaderRead::Failed,
+    }
+}
+
+/// Reads the card in a reader. Unless `fresh` is `true`, the card read before, or the card found unsupported before, is returned without accessing it if the event counter of the reader has not changed.
+fn read_reader(backend: &dyn CardBackend, reader: &str, fresh: bool) -> ReaderRead {
+    let event_count = backend.card_event_count(reader);
+
+    if !fresh {
+        if let Some(mut record) = session::cached(reader, event_count) {
+            derive_fields(&mut record);
+
+            metrics::record_card_read(CardReadResult::Cached);
+
+            return ReaderRead::Card(Box::new(record));
+        }
+
+        if session::is_unsupported(reader, event_count) {
+            metrics::record_card_read(CardReadResult::Cached);
+
+            return ReaderRead::Unsupported;
+        }
+    }
+
+    let cooperative = cooperative::is_enabled();
+
+    if cooperative {
+        if backend.card_in_use(reader) {
+            cooperative::mark_in_use(reader);
+        }
 
-    let (readers, readers_cs) = {
-        let mut v = Vec::with_capacity(1);
-        let mut v_cs = Vec::with_capacity(1);
+        if cooperative::is_busy(reader) {
+            metrics::record_card_read(CardReadResult::Busy);
 
-        for name in names {
-            v.push(name.to_string_lossy().into_owned());
-            v_cs.push(name);
+            return ReaderRead::Busy;
         }
+    }
+
+    let mut card = match backend.connect(reader) {
+        Ok(card) => card,
+        Err(error) if is_removed_error(error) => {
+            session::end(reader);
 
-        (v, v_cs)
+            metrics::record_card_read(CardReadResult::NoCard);
+
+            return ReaderRead::Empty;
+        },
+        Err(error) if cooperative && cooperative::is_busy_error(error) => {
+            cooperative::back_off(reader);
+
+            metrics::record_card_read(CardReadResult::Busy);
+
+            return ReaderRead::Busy;
+        },
+        Err(error) => {
+            tracing::warn!(target: "card", reader, ?error);
+
+            metrics::record_card_read(CardReadResult::Error);
+
+            return read_failed(reader, event_count);
+        },
     };
 
-    let mut buffer = [0u8; 59];
+    let read = if cooperative {
+        let mut read = ReaderRead::Failed;
 
-    for (reader, reader_cs) in readers.into_iter().zip(readers_cs) {
-        let card = match context.connect(reader_cs, ShareMode::Shared, Protocols::ANY) {
-            Ok(card) => card,
-            Err(pcsc::Error::NoSmartcard | pcsc::Error::RemovedCard) => {
-                continue;
-            },
-            Err(error) => {
-                tracing::warn!(target: "card", reader, ?error);
+        match card.transaction(&mut |card| read = read_card(card, reader)) {
+            Ok(()) if !matches!(read, ReaderRead::Busy) => read,
+            Ok(()) => {
+                cooperative::back_off(reader);
 
-                continue;
-            },
-        };
+                metrics::record_card_read(CardReadResult::Busy);
 
-        match card.transmit(APDU_SELECT, &mut buffer) {
-            Ok([144, 0]) => {
-                // pass
+                return ReaderRead::Busy;
             },
-            Ok(_) => {
-                tracing::warn!(target: "card", reader, "unsupported reader");
+            Err(error) if cooperative::is_busy_error(error) => {
+                cooperative::back_off(reader);
 
-                continue;
+                metrics::record_card_read(CardReadResult::Busy);
+
+                return ReaderRead::Busy;
             },
             Err(error) => {
-                tracing::warn!(target: "card", reader, ?error);
+                tracing::warn!(target: "card", reader, ?error, "the transaction failed");
+
+                metrics::record_card_read(CardReadResult::Error);
 
-                continue;
+                return read_failed(reader, event_count);
             },
         }
+    } else {
+        read_card(card.as_mut(), reader)
+    };
 
-        match card.transmit(APDU_READ, &mut buffer) {
-            Ok(result) => match NHICardBasic::from_raw(result) {
-                Ok(mut basic) => {
-                    basic.reader_name = Some(reader.clone());
+    drop(card);
 
-                    NHI_CARD_LIST.push(basic);
-                },
-                Err(error) => {
-                    tracing::warn!(target: "card", reader, ?error);
+    if cooperative && !matches!(read, ReaderRead::Failed) {
+        cooperative::clear(reader);
+    }
 
-                    continue;
-                },
-            },
-            Err(error) => {
-                tracing::warn!(target: "card", reader, ?error);
+    let mut record = match read {
+        ReaderRead::Card(record) => record,
+        ReaderRead::Empty => {
+            session::end(reader);
 
-                continue;
-            },
+            return ReaderRead::Empty;
+        },
+        ReaderRead::Unsupported => {
+            session::mark_unsupported(reader, event_count);
+
+            return ReaderRead::Unsupported;
+        },
+        ReaderRead::Failed => return read_failed(reader, event_count),
+        read => return read,
+    };
+
+    if matches!(record.as_ref(), CardRecord::Nhi(basic) if basic.name_has_unmapped_chars) {
+        tracing::warn!(target: "card", reader, "the name has unmapped characters");
+    }
+
+    record.set_reader_name(String::from(reader));
+
+    session::assign(reader, &mut record, event_count);
+
+    derive_fields(&mut record);
+
+    metrics::record_card_read(CardReadResult::Ok);
+
+    ReaderRead::Card(record)
+}
+
+async fn read_reader_with_timeout(reader: String, timeout: Duration, fresh: bool) -> ReaderRead {
+    if !READING_READERS.lock().unwrap().insert(reader.clone()) {
+        tracing::warn!(target: "card", reader, "the previous read has not finished");
+
+        metrics::CARD_READ_TIMEOUTS.inc();
+
+        return ReaderRead::TimedOut;
+    }
+
+    let backend = backend();
+    let reader_name = reader.clone();
+
+    // the blocking thread cannot be cancelled, so the reader is marked as being read until it finishes
+    let handle = task::spawn_blocking(move || {
+        let card = read_reader(backend.as_ref(), &reader_name, fresh);
+
+        READING_READERS.lock().unwrap().remove(&reader_name);
+
+        card
+    });
+
+    match time::timeout(timeout, handle).await {
+        Ok(result) => result.unwrap(),
+        Err(_) => {
+            tracing::warn!(target: "card", reader, "the reader timed out");
+
+            metrics::CARD_READ_TIMEOUTS.inc();
+
+            ReaderRead::TimedOut
+        },
+    }
+}
+
+#[derive(Debug, Default)]
+struct CardUpdate {
+    reader_names:      Vec<String>,
+    timed_out_readers: Vec<String>,
+    busy_readers:      Vec<String>,
+    failed_readers:    Vec<String>,
+}
+
+/// Reads all readers concurrently.
+async fn update_cards(fresh: bool) -> Result<CardUpdate, pcsc::Error> {
+    debug_assert!(LOCK.try_lock().is_err());
+
+    unsafe {
+        (*addr_of_mut!(CARD_LIST)).clear();
+    }
+
+    let backend = backend();
+
+    let readers = task::spawn_blocking(move || list_readers(backend.as_ref())).await.unwrap()?;
+
+    session::retain(&readers);
+    cooperative::retain(&readers);
+
+    let timeout = Duration::from_millis(READER_TIMEOUT_MILLIS.load(Ordering::Relaxed));
+
+    let results = future::join_all(
+        readers.iter().map(|reader| read_reader_with_timeout(reader.clone(), timeout, fresh)),
+    )
+    .await;
+
+    let mut cards = Vec::new();
+    let mut update = CardUpdate::default();
+
+    for (reader, result) in readers.iter().zip(results) {
+        match result {
+            ReaderRead::Card(card) => cards.push(*card),
+            ReaderRead::Empty | ReaderRead::Unsupported => (),
+            ReaderRead::Failed => update.failed_readers.push(reader.clone()),
+            ReaderRead::Busy => update.busy_readers.push(reader.clone()),
+            ReaderRead::TimedOut => update.timed_out_readers.push(reader.clone()),
         }
     }
 
-    Ok(())
+    unsafe {
+        *addr_of_mut!(CARD_LIST) = cards;
+    }
+
+    update.reader_names = readers;
+
+    Ok(update)
 }
 
-pub async fn fetch_nhi_cards_json_string() -> Result<String, pcsc::Error> {
+/// Reads the cards of all types in all readers. The cards which have not been removed since the last read are served from memory unless `fresh` is `true`.
+pub async fn fetch_cards(fresh: bool) -> Result<Vec<CardRecord>, pcsc::Error> {
     let lock_get = LOCK_GET.lock().await;
-    let lock_result = LOCK.try_lock();
+
+    // a fresh read waits for the ongoing update to finish and then runs its own, since the ongoing one may be served from memory
+    let lock_result = if fresh { Ok(LOCK.lock().await) } else { LOCK.try_lock() };
 
     drop(lock_get);
 
     match lock_result {
         Ok(lock) => {
-            let lock = unsafe {
-                if CONTEXT.is_none() {
-                    CONTEXT = Some(Context::establish(Scope::User)?);
-                }
-
-                // Move the lock to the synchronized block to prevent the lock being released when executing the synchronized block and the HTTP connection is being disconnected.
-                task::spawn_blocking(move || update_nhi_cards(false).map(|_| lock))
+            // Move the lock to a separate task to prevent the lock being released when executing the update and the HTTP connection is being disconnected.
+            let result =
+                task::spawn(async move { update_cards(fresh).await.map(|update| (update, lock)) })
                     .await
-                    .unwrap()?
+                    .unwrap();
+
+            let (update, lock) = match result {
+                Ok(result) => result,
+                Err(error) => {
+                    status::record_error(error);
+
+                    return Err(error);
+                },
             };
 
-            let json = serde_json::to_string(unsafe { &NHI_CARD_LIST }).unwrap();
+            let cards = unsafe { (*addr_of!(CARD_LIST)).clone() };
 
             drop(lock);
 
-            Ok(json)
+            let unchanged_readers = [
+                update.timed_out_readers.as_slice(),
+                update.busy_readers.as_slice(),
+                update.failed_readers.as_slice(),
+            ]
+            .concat();
+
+            events::detect_changes(&nhi_cards(cards.clone()), &unchanged_readers);
+
+            status::record_success(
+                update.reader_names,
+                update.timed_out_readers,
+                update.busy_readers,
+            );
+
+            Ok(cards)
         },
-        Err(_) => Ok(get_nhi_cards_json_string().await),
+        Err(_) => Ok(get_cards().await),
     }
 }
 
+/// Reads the NHI cards in all readers. See `fetch_cards`.
 #[inline]
-pub async fn get_nhi_cards_json_string() -> String {
+pub async fn fetch_nhi_cards(fresh: bool) -> Result<Vec<NHICardBasic>, pcsc::Error> {
+    fetch_cards(fresh).await.map(nhi_cards)
+}
+
+#[inline]
+pub async fn get_cards() -> Vec<CardRecord> {
     let lock_get = LOCK_GET.lock().await;
     let lock = LOCK.lock().await;
 
-    let json = serde_json::to_string(unsafe { &NHI_CARD_LIST }).unwrap();
+    let cards = unsafe { (*addr_of!(CARD_LIST)).clone() };
 
     drop(lock);
     drop(lock_get);
 
-    json
+    cards
+}
+
+#[inline]
+fn nhi_cards(cards: Vec<CardRecord>) -> Vec<NHICardBasic> {
+    cards
+        .into_iter()
+        .filter_map(|card| match card {
+            CardRecord::Nhi(basic) => Some(*basic),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_failed() {
+        let reader = "Failing Reader";
+
+        assert!(matches!(super::read_failed(reader, Some(1)), ReaderRead::Empty));
+
+        let mut record = CardRecord::Nhi(Box::new(NHICardBasic::builder().build().unwrap()));
+
+        session::assign(reader, &mut record, Some(1));
+
+        // the card is kept while the event counter stays the same
+        let ReaderRead::Card(card) = super::read_failed(reader, Some(1)) else {
+            panic!("the card is not kept");
+        };
+
+        assert_eq!(record.card_id(), card.card_id());
+
+        // the card may have been swapped
+        assert!(matches!(super::read_failed(reader, Some(3)), ReaderRead::Empty));
+        assert!(session::cached(reader, Some(1)).is_none());
+    }
 }
@@ -0,0 +1,237 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use tokio::{task, time};
+
+use super::{backend, transmit_timed, READER_TIMEOUT_MILLIS, READING_READERS};
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A `CLA:INS` pair such as `00:B0`. `*` matches any byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApduPattern {
+    cla: Option<u8>,
+    ins: Option<u8>,
+}
+
+impl ApduPattern {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        fn parse_byte(s: &str) -> Result<Option<u8>, String> {
+            match s.trim() {
+                "*" => Ok(None),
+                s => u8::from_str_radix(s, 16)
+                    .map(Some)
+                    .map_err(|_| format!("{s:?} 不是十六進位的位元組")),
+            }
+        }
+
+        let (cla, ins) = pattern.split_once(':').ok_or("格式須為 CLA:INS，例如 00:B0")?;
+
+        Ok(Self {
+            cla: parse_byte(cla)?, ins: parse_byte(ins)?
+        })
+    }
+
+    #[inline]
+    fn matches(&self, apdu: &[u8]) -> bool {
+        self.cla.map_or(true, |cla| cla == apdu[0]) && self.ins.map_or(true, |ins| ins == apdu[1])
+    }
+}
+
+#[derive(Debug)]
+pub enum PassthroughError {
+    /// The APDU at the index is not a hex string of at least 4 bytes.
+    InvalidApdu(usize),
+    /// The CLA and INS of the APDU at the index are not in the allowlist.
+    NotAllowed(usize),
+    /// The reader kept being read by this service.
+    Busy,
+    /// The card did not respond within the reader timeout.
+    TimedOut,
+    Pcsc(pcsc::Error),
+}
+
+impl Display for PassthroughError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidApdu(index) => write!(f, "第 {index} 個 APDU 不是正確的十六進位指令"),
+            Self::NotAllowed(index) => write!(f, "第 {index} 個 APDU 的 CLA 與 INS 不在允許清單中"),
+            Self::Busy => f.write_str("讀卡機正在被讀取，請稍後再試"),
+            Self::TimedOut => f.write_str("讀卡機沒有回應"),
+            Self::Pcsc(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for PassthroughError {}
+
+impl From<pcsc::Error> for PassthroughError {
+    #[inline]
+    fn from(error: pcsc::Error) -> Self {
+        Self::Pcsc(error)
+    }
+}
+
+/// Decodes the hex APDUs and checks them against the allowlist before anything is sent to the card.
+pub fn parse_apdus(
+    apdus: &[String],
+    allowlist: &[ApduPattern],
+) -> Result<Vec<Vec<u8>>, PassthroughError> {
+    apdus
+        .iter()
+        .enumerate()
+        .map(|(index, apdu)| {
+            let apdu = match hex::decode(apdu.trim()) {
+                Ok(apdu) if apdu.len() >= 4 => apdu,
+                _ => return Err(PassthroughError::InvalidApdu(index)),
+            };
+
+            if !allowlist.iter().any(|pattern| pattern.matches(&apdu)) {
+                return Err(PassthroughError::NotAllowed(index));
+            }
+
+            Ok(apdu)
+        })
+        .collect()
+}
+
+/// Removes the reader from the readers being read when the passthrough finishes.
+struct ReadingGuard(String);
+
+impl Drop for ReadingGuard {
+    #[inline]
+    fn drop(&mut self) {
+        READING_READERS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Transmits the APDUs to the card in a reader in one transaction and returns the responses, including the status words. Reading the reader is held off meanwhile, so that the selected applet is not switched in the middle of a read.
+pub async fn transmit_apdus(
+    reader: String,
+    apdus: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, PassthroughError> {
+    let timeout = Duration::from_millis(READER_TIMEOUT_MILLIS.load(Ordering::Relaxed));
+
+    let wait = async {
+        loop {
+            let held = READING_READERS.lock().unwrap().insert(reader.clone());
+
+            if held {
+                break;
+            }
+
+            time::sleep(WAIT_INTERVAL).await;
+        }
+    };
+
+    if time::timeout(timeout, wait).await.is_err() {
+        return Err(PassthroughError::Busy);
+    }
+
+    let guard = ReadingGuard(reader);
+
+    // the blocking thread cannot be cancelled, so the reader is held off until it finishes
+    let handle = task::spawn_blocking(move || {
+        let reader = guard.0.as_str();
+
+        let mut card = backend().connect_unrecorded(reader)?;
+
+        let mut result = Ok(Vec::with_capacity(apdus.len()));
+
+        card.transaction(&mut |card| {
+            result = apdus.iter().map(|apdu| transmit_timed(card, "passthrough", apdu)).collect();
+        })?;
+
+        tracing::info!(target: "card", reader, count = apdus.len(), "passthrough");
+
+        Ok(result?)
+    });
+
+    match time::timeout(timeout, handle).await {
+        Ok(result) => result.unwrap(),
+        Err(_) => {
+            tracing::warn!(target: "card", "the passthrough timed out");
+
+            Err(PassthroughError::TimedOut)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pattern() {
+        assert_eq!(
+            ApduPattern {
+                cla: Some(0x00), ins: Some(0xB0)
+            },
+            ApduPattern::parse("00:B0").unwrap()
+        );
+        assert_eq!(
+            ApduPattern {
+                cla: None, ins: Some(0xA4)
+            },
+            ApduPattern::parse(" * : a4 ").unwrap()
+        );
+        assert_eq!(
+            ApduPattern {
+                cla: None, ins: None
+            },
+            ApduPattern::parse("*:*").unwrap()
+        );
+
+        assert!(ApduPattern::parse("00B0").is_err());
+        assert!(ApduPattern::parse("00:").is_err());
+        assert!(ApduPattern::parse("00:100").is_err());
+        assert!(ApduPattern::parse("0G:B0").is_err());
+        assert!(ApduPattern::parse("00:B0:00").is_err());
+    }
+
+    #[test]
+    fn parse_apdus_with_allowlist() {
+        let allowlist = [ApduPattern::parse("00:B0").unwrap(), ApduPattern::parse("*:CA").unwrap()];
+
+        let apdus = [String::from("00B0000000"), String::from(" 80CA9F7F00 ")];
+
+        assert_eq!(
+            vec![vec![0x00, 0xB0, 0x00, 0x00, 0x00], vec![0x80, 0xCA, 0x9F, 0x7F, 0x00]],
+            parse_apdus(&apdus, &allowlist).unwrap()
+        );
+
+        // CLA must match unless it is a wildcard
+        assert!(matches!(
+            parse_apdus(&[String::from("80B0000000")], &allowlist),
+            Err(PassthroughError::NotAllowed(0))
+        ));
+        // VERIFY is not in the allowlist
+        assert!(matches!(
+            parse_apdus(&[String::from("00B0000000"), String::from("0020008108")], &allowlist),
+            Err(PassthroughError::NotAllowed(1))
+        ));
+        // shorter than 4 bytes
+        assert!(matches!(
+            parse_apdus(&[String::from("00B000")], &allowlist),
+            Err(PassthroughError::InvalidApdu(0))
+        ));
+        assert!(matches!(
+            parse_apdus(&[String::from("00B0000")], &allowlist),
+            Err(PassthroughError::InvalidApdu(0))
+        ));
+        assert!(matches!(
+            parse_apdus(&[String::from("not hex!")], &allowlist),
+            Err(PassthroughError::InvalidApdu(0))
+        ));
+        // nothing is allowed with an empty allowlist
+        assert!(matches!(
+            parse_apdus(&[String::from("00B0000000")], &[]),
+            Err(PassthroughError::NotAllowed(0))
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use tokio::{task, time};
+
+use super::{backend, fetch_cards};
+
+/// The readers are read at most this often, since connecting to a card also changes the state of its reader.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Reads the readers whenever a reader or a card changes, or at least every `interval`, so that card events are emitted without any client requesting the cards.
+pub fn spawn_card_poller(interval: Duration) {
+    task::spawn(async move {
+        loop {
+            let result =
+                task::spawn_blocking(move || backend().wait_for_change(interval)).await.unwrap();
+
+            if let Err(error) = result {
+                tracing::debug!(target: "card", ?error, "cannot wait for reader changes");
+
+                time::sleep(interval).await;
+            }
+
+            if let Err(error) = fetch_cards(false).await {
+                tracing::debug!(target: "card", ?error, "polling failed");
+            }
+
+            time::sleep(MIN_POLL_INTERVAL).await;
+        }
+    });
+}
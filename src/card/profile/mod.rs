@@ -0,0 +1,61 @@
+mod moica;
+mod nhi;
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+pub use moica::MoicaProfile;
+pub use nhi::NhiProfile;
+pub(super) use nhi::{APDU_READ, APDU_SELECT};
+
+use super::{CardConnection, CardRecord};
+
+/// The profiles which are tried in order for every card.
+pub static PROFILES: &[&dyn CardProfile] = &[&NhiProfile, &MoicaProfile];
+
+/// A type of card. A profile reads the cards whose application can be selected with its SELECT APDU.
+pub trait CardProfile: Send + Sync {
+    /// The `card_type` of the records read by this profile.
+    fn card_type(&self) -> &'static str;
+
+    fn select_apdu(&self) -> &'static [u8];
+
+    /// Reads the card after its application is selected.
+    fn read(&self, card: &mut dyn CardConnection) -> Result<CardRecord, ProfileError>;
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Pcsc(pcsc::Error),
+    /// The card responds with an unexpected status word.
+    Status([u8; 2]),
+    Parse(Box<dyn Error + Send + Sync>),
+}
+
+impl Display for ProfileError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pcsc(error) => Display::fmt(error, f),
+            Self::Status([sw1, sw2]) => write!(f, "卡片回應了 {sw1:02X}{sw2:02X}"),
+            Self::Parse(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+impl From<pcsc::Error> for ProfileError {
+    #[inline]
+    fn from(error: pcsc::Error) -> Self {
+        Self::Pcsc(error)
+    }
+}
+
+/// Whether the response of a SELECT APDU means the file or the application is selected. `61XX` means more response data is available.
+#[inline]
+pub fn is_selected(response: &[u8]) -> bool {
+    matches!(response, [.., 0x90, 0x00] | [.., 0x61, _])
+}
@@ -0,0 +1,175 @@
+use super::{is_selected, CardProfile, ProfileError};
+use crate::card::{transmit_timed, CardConnection, CardRecord, MOICACard, MOICACertificate};
+
+/// Selects the PKCS #15 application.
+const APDU_SELECT: &[u8] = b"\x00\xA4\x04\x00\x0C\xA0\x00\x00\x00\x63PKCS-15";
+/// The object directory file, which is under the PKCS #15 application.
+const ODF_PATH: &[u8] = &[0x50, 0x31];
+const READ_CHUNK_SIZE: u8 = 0xE0;
+/// READ BINARY addresses at most 15 bits of offset.
+const MAX_FILE_SIZE: usize = 0x7FFF;
+
+/// The MOICA card (自然人憑證). The certificates are found via the PKCS #15 directories and read without the PIN.
+pub struct MoicaProfile;
+
+/// Selects a file by a PKCS #15 path, which is either absolute from the MF (`3F00`) or relative to the current DF.
+fn select_path(card: &mut dyn CardConnection, path: &[u8]) -> Result<(), ProfileError> {
+    let (p1, path) = match path {
+        [0x3F, 0x00, rest @ ..] if !rest.is_empty() => (0x08, rest),
+        [_, _] => (0x02, path),
+        _ => (0x09, path),
+    };
+
+    let apdu = [&[0x00, 0xA4, p1, 0x00, path.len() as u8], path].concat();
+
+    let response = transmit_timed(card, "select", &apdu)?;
+
+    if is_selected(&response) {
+        Ok(())
+    } else {
+        Err(status_error(&response))
+    }
+}
+
+#[inline]
+fn status_error(response: &[u8]) -> ProfileError {
+    match response {
+        [.., sw1, sw2] => ProfileError::Status([*sw1, *sw2]),
+        _ => ProfileError::Pcsc(pcsc::Error::InvalidValue),
+    }
+}
+
+/// Reads the selected transparent file. If `der` is `true`, the file holds one DER element and reading stops at its end instead of the end of the file.
+fn read_file(card: &mut dyn CardConnection, der: bool) -> Result<Vec<u8>, ProfileError> {
+    let mut data = Vec::new();
+    let mut le = READ_CHUNK_SIZE;
+
+    while data.len() < MAX_FILE_SIZE {
+        if der {
+            if let Some(length) = MOICACertificate::encoded_length(&data) {
+                if data.len() >= length {
+                    break;
+                }
+            }
+        }
+
+        let [offset_high, offset_low] = (data.len() as u16).to_be_bytes();
+
+        let mut response =
+            transmit_timed(card, "read", &[0x00, 0xB0, offset_high, offset_low, le])?;
+
+        let Some(sw) = response.len().checked_sub(2).map(|i| response.split_off(i)) else {
+            return Err(status_error(&response));
+        };
+
+        match sw.as_slice() {
+            [0x90, 0x00] => {
+                let finished = response.len() < le as usize;
+
+                data.extend_from_slice(&response);
+
+                if finished {
+                    break;
+                }
+
+                le = READ_CHUNK_SIZE;
+            },
+            // the end of the file is reached
+            [0x62, 0x82] => {
+                data.extend_from_slice(&response);
+
+                break;
+            },
+            // the offset is beyond the end of the file
+            [0x6B, 0x00] if !data.is_empty() => break,
+            // wrong Le, and the right one is given, which is tried once for each chunk. `00` means 256 bytes, which is more than a chunk.
+            [0x6C, n] if *n != 0 && *n != le && le == READ_CHUNK_SIZE => le = *n,
+            _ => return Err(status_error(&sw)),
+        }
+    }
+
+    Ok(data)
+}
+
+impl CardProfile for MoicaProfile {
+    #[inline]
+    fn card_type(&self) -> &'static str {
+        "moica"
+    }
+
+    #[inline]
+    fn select_apdu(&self) -> &'static [u8] {
+        APDU_SELECT
+    }
+
+    fn read(&self, card: &mut dyn CardConnection) -> Result<CardRecord, ProfileError> {
+        select_path(card, ODF_PATH)?;
+
+        let odf = read_file(card, false)?;
+
+        let mut certificates = Vec::new();
+
+        for cdf_path in MOICACard::parse_odf(odf) {
+            select_path(card, &cdf_path)?;
+
+            let cdf = read_file(card, false)?;
+
+            for (label, path) in MOICACard::parse_cdf(cdf) {
+                select_path(card, &path)?;
+
+                let data = read_file(card, true)?;
+
+                certificates.push(
+                    MOICACertificate::from_der(label, data)
+                        .map_err(|error| ProfileError::Parse(Box::new(error)))?,
+                );
+            }
+        }
+
+        if certificates.is_empty() {
+            return Err(ProfileError::Parse("沒有任何憑證".into()));
+        }
+
+        Ok(CardRecord::Moica(MOICACard {
+            reader_name: None,
+            certificates,
+            session_id: None,
+            inserted_at: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Responds to every APDU with the same status word and counts the APDUs.
+    struct StatusConnection([u8; 2], usize);
+
+    impl CardConnection for StatusConnection {
+        fn transmit(&mut self, _apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+            self.1 += 1;
+
+            Ok(self.0.to_vec())
+        }
+
+        fn transaction(
+            &mut self,
+            f: &mut dyn FnMut(&mut dyn CardConnection),
+        ) -> Result<(), pcsc::Error> {
+            f(self);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_file_with_wrong_le() {
+        for sw in [[0x6C, 0x00], [0x6C, 0x10]] {
+            let mut card = StatusConnection(sw, 0);
+
+            assert!(matches!(read_file(&mut card, false), Err(ProfileError::Status(_))));
+            assert!(card.1 <= 2);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::{CardProfile, ProfileError};
+use crate::card::{
+    transmit_timed, CardConnection, CardRecord, NHICardBasic, NameDecoding, NAME_DECODING,
+};
+
+pub(in crate::card) const APDU_SELECT: &[u8] =
+    b"\x00\xA4\x04\x00\x10\xD1\x58\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x11\x00";
+pub(in crate::card) const APDU_READ: &[u8] = b"\x00\xCA\x11\x00\x02\x00\x00";
+
+/// The NHI card (健保卡). The basic data is read with one APDU.
+pub struct NhiProfile;
+
+impl CardProfile for NhiProfile {
+    #[inline]
+    fn card_type(&self) -> &'static str {
+        "nhi"
+    }
+
+    #[inline]
+    fn select_apdu(&self) -> &'static [u8] {
+        APDU_SELECT
+    }
+
+    fn read(&self, card: &mut dyn CardConnection) -> Result<CardRecord, ProfileError> {
+        let result = transmit_timed(card, "read", APDU_READ)?;
+
+        NHICardBasic::from_raw_with_name_decoding(
+            result,
+            NAME_DECODING.get_or_init(NameDecoding::default),
+        )
+        .map(|basic| CardRecord::Nhi(Box::new(basic)))
+        .map_err(|error| ProfileError::Parse(Box::new(error)))
+    }
+}
@@ -0,0 +1,590 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    backend::{CardBackend, CardConnection},
+    APDU_READ,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum RecordEntry {
+    Readers {
+        time:    DateTime<Local>,
+        #[serde(default)]
+        readers: Vec<String>,
+        error:   Option<String>,
+    },
+    Connect {
+        reader: String,
+        error:  Option<String>,
+    },
+    Transmit {
+        reader:   String,
+        command:  String,
+        response: Option<String>,
+        error:    Option<String>,
+    },
+}
+
+#[inline]
+fn error_name(error: pcsc::Error) -> String {
+    format!("{error:?}")
+}
+
+/// Whether a Big5 code is one of the frequently or less frequently used characters, rather than a symbol, a user-defined character or an extension such as the ones in `C6A1`–`C8FE` and `F9D6`–`F9FE`.
+#[inline]
+fn is_common_big5(code: u16) -> bool {
+    matches!(code, 0xA440..=0xC67E | 0xC940..=0xF9D5)
+}
+
+/// The characters which scrambled names are made of.
+const NAME_CHARS: &str = "王陳林張李黃吳劉蔡楊明華美文志玉淑惠芳雅";
+
+/// The DER encodings of the OIDs of the `commonName` and `serialNumber` attributes.
+const OID_NAME_ATTRIBUTES: [&[u8]; 2] =
+    [&[0x06, 0x03, 0x55, 0x04, 0x03], &[0x06, 0x03, 0x55, 0x04, 0x05]];
+
+const TAG_BMP_STRING: u8 = 0x1E;
+
+/// Replaces the personal data in a basic data response or in certificates with pseudo-random data of the same kind, so that a recording can be shared.
+///
+/// Digits stay digits and common Big5 characters stay common Big5 characters. Bytes in the user-defined and extended areas of Big5 are kept, since they are usually why a name cannot be parsed. Dates and the sex are kept.
+struct Scrambler {
+    seed:       [u8; 32],
+    counter:    u64,
+    names:      Vec<[u8; 2]>,
+    name_chars: Vec<char>,
+}
+
+impl Scrambler {
+    fn new() -> Self {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+
+        let (names, ..) = encoding_rs::BIG5.encode(NAME_CHARS);
+
+        Self {
+            seed:       Sha256::digest(now.as_nanos().to_le_bytes()).into(),
+            counter:    0,
+            names:      names.chunks_exact(2).map(|c| [c[0], c[1]]).collect(),
+            name_chars: NAME_CHARS.chars().collect(),
+        }
+    }
+
+    fn next(&mut self) -> u8 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seed);
+        hasher.update(self.counter.to_le_bytes());
+
+        self.counter += 1;
+
+        hasher.finalize()[0]
+    }
+
+    fn scramble_digits(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut().filter(|b| b.is_ascii_digit()) {
+            *b = b'0' + self.next() % 10;
+        }
+    }
+
+    fn scramble_basic_data(&mut self, data: &mut [u8]) {
+        if data.len() < 57 {
+            return;
+        }
+
+        // card number
+        self.scramble_digits(&mut data[..12]);
+
+        // full name, which may end with half of a character
+        let mut i = 12;
+
+        while i < 32 && data[i] != 0 {
+            if data[i] < 0x80 {
+                i += 1;
+
+                continue;
+            }
+
+            let trail = if i + 1 < 32 { data[i + 1] } else { 0x40 };
+
+            if is_common_big5(u16::from_be_bytes([data[i], trail])) {
+                let index = self.next() as usize % self.names.len();
+                let name = self.names[index];
+
+                let end = (i + 2).min(32);
+
+                data[i..end].copy_from_slice(&name[..end - i]);
+            }
+
+            i += 2;
+        }
+
+        // ID number, keeping the area letter and the sex digit
+        self.scramble_digits(&mut data[34..42]);
+    }
+
+    /// Replaces a character with a random one of the same kind which is encoded in the same length in UTF-8 and UTF-16.
+    fn scramble_char(&mut self, c: char) -> char {
+        match c {
+            '0'..='9' => (b'0' + self.next() % 10) as char,
+            'A'..='Z' => (b'A' + self.next() % 26) as char,
+            'a'..='z' => (b'a' + self.next() % 26) as char,
+            '\u{4E00}'..='\u{9FFF}' => {
+                let index = self.next() as usize % self.name_chars.len();
+
+                self.name_chars[index]
+            },
+            _ => c,
+        }
+    }
+
+    /// Replaces the `commonName` and `serialNumber` attributes, i.e. the name and a part of the ID number, of the certificates in DER. The attributes are located by their OIDs, so the certificates can be incomplete.
+    fn scramble_certificates(&mut self, data: &mut [u8]) {
+        let mut i = 0;
+
+        while i < data.len() {
+            if !OID_NAME_ATTRIBUTES.iter().any(|oid| data[i..].starts_with(oid)) {
+                i += 1;
+
+                continue;
+            }
+
+            i += OID_NAME_ATTRIBUTES[0].len();
+
+            // only the short form of length is used by the attributes
+            let (Some(&tag), Some(&length)) = (data.get(i), data.get(i + 1)) else {
+                break;
+            };
+
+            let start = i + 2;
+            let end = start + length as usize;
+
+            if length >= 0x80 || end > data.len() {
+                continue;
+            }
+
+            let value = &mut data[start..end];
+
+            match tag {
+                TAG_BMP_STRING => {
+                    let units = value
+                        .chunks_exact(2)
+                        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                        .collect::<Vec<_>>();
+
+                    let scrambled = char::decode_utf16(units)
+                        .map(|c| {
+                            c.map(|c| self.scramble_char(c)).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        })
+                        .collect::<String>();
+
+                    let units =
+                        scrambled.encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<_>>();
+
+                    if units.len() == value.len() {
+                        value.copy_from_slice(&units);
+                    }
+                },
+                _ => {
+                    // UTF8String, PrintableString and so on
+                    if let Ok(s) = std::str::from_utf8(value) {
+                        let scrambled =
+                            s.chars().map(|c| self.scramble_char(c)).collect::<String>();
+
+                        value.copy_from_slice(scrambled.as_bytes());
+                    }
+                },
+            }
+
+            i = end;
+        }
+    }
+}
+
+pub struct RecordingBackend {
+    inner:     Box<dyn CardBackend>,
+    writer:    Arc<Mutex<BufWriter<File>>>,
+    scrambler: Option<Arc<Mutex<Scrambler>>>,
+}
+
+fn write_entry(writer: &Mutex<BufWriter<File>>, entry: &RecordEntry) {
+    let mut writer = writer.lock().unwrap();
+
+    let result = serde_json::to_writer(&mut *writer, entry)
+        .map_err(io::Error::from)
+        .and_then(|_| writer.write_all(b"\n"))
+        .and_then(|_| writer.flush());
+
+    if let Err(error) = result {
+        tracing::error!(target: "card", ?error, "cannot write the recording");
+    }
+}
+
+impl RecordingBackend {
+    pub fn new<P: AsRef<Path>>(
+        inner: Box<dyn CardBackend>,
+        path: P,
+        scramble: bool,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            inner,
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+            scrambler: scramble.then(|| Arc::new(Mutex::new(Scrambler::new()))),
+        })
+    }
+}
+
+impl CardBackend for RecordingBackend {
+    #[inline]
+    fn reestablish(&self) -> Result<(), pcsc::Error> {
+        self.inner.reestablish()
+    }
+
+    fn list_readers(&self) -> Result<Vec<String>, pcsc::Error> {
+        let result = self.inner.list_readers();
+
+        write_entry(&self.writer, &RecordEntry::Readers {
+            time:    Local::now(),
+            readers: result.as_ref().cloned().unwrap_or_default(),
+            error:   result.as_ref().err().copied().map(error_name),
+        });
+
+        result
+    }
+
+    #[inline]
+    fn card_event_count(&self, reader: &str) -> Option<u32> {
+        self.inner.card_event_count(reader)
+    }
+
+    #[inline]
+    fn card_in_use(&self, reader: &str) -> bool {
+        self.inner.card_in_use(reader)
+    }
+
+    #[inline]
+    fn wait_for_change(&self, timeout: Duration) -> Result<(), pcsc::Error> {
+        self.inner.wait_for_change(timeout)
+    }
+
+    #[inline]
+    fn connect_unrecorded(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        self.inner.connect_unrecorded(reader)
+    }
+
+    fn connect(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        let result = self.inner.connect(reader);
+
+        write_entry(&self.writer, &RecordEntry::Connect {
+            reader: String::from(reader),
+            error:  result.as_ref().err().copied().map(error_name),
+        });
+
+        Ok(Box::new(RecordingConnection {
+            inner:    result?,
+            recorder: Recorder {
+                reader:    String::from(reader),
+                writer:    self.writer.clone(),
+                scrambler: self.scrambler.clone(),
+                pending:   Mutex::new(Vec::new()),
+            },
+        }))
+    }
+}
+
+/// A command and its response.
+pub(super) type Exchange = (Vec<u8>, Result<Vec<u8>, pcsc::Error>);
+
+/// READ BINARY, which reads a transparent file, e.g. a certificate.
+const INS_READ_BINARY: u8 = 0xB0;
+
+struct Recorder {
+    reader:    String,
+    writer:    Arc<Mutex<BufWriter<File>>>,
+    scrambler: Option<Arc<Mutex<Scrambler>>>,
+    /// The READ BINARY exchanges of the selected file when scrambling. A file is read in chunks, so the exchanges are written after the whole file is read, when the certificates in it can be found and scrambled.
+    pending:   Mutex<Vec<Exchange>>,
+}
+
+impl Recorder {
+    fn transmit(
+        &self,
+        inner: &mut dyn CardConnection,
+        apdu: &[u8],
+    ) -> Result<Vec<u8>, pcsc::Error> {
+        let result = inner.transmit(apdu);
+
+        if self.scrambler.is_some() && apdu.get(1) == Some(&INS_READ_BINARY) {
+            self.pending.lock().unwrap().push((apdu.to_vec(), result.clone()));
+
+            return result;
+        }
+
+        self.flush();
+
+        let response = result.as_ref().ok().map(|response| match self.scrambler.as_ref() {
+            Some(scrambler) if apdu == APDU_READ => {
+                let mut response = response.clone();
+
+                scrambler.lock().unwrap().scramble_basic_data(&mut response);
+
+                hex::encode_upper(response)
+            },
+            _ => hex::encode_upper(response),
+        });
+
+        self.write_transmit(apdu, response, result.as_ref().err().copied());
+
+        result
+    }
+
+    fn write_transmit(&self, apdu: &[u8], response: Option<String>, error: Option<pcsc::Error>) {
+        write_entry(&self.writer, &RecordEntry::Transmit {
+            reader: self.reader.clone(),
+            command: hex::encode_upper(apdu),
+            response,
+            error: error.map(error_name),
+        });
+    }
+
+    /// Scrambles the certificates in the file which has been read, and writes its exchanges.
+    fn flush(&self) {
+        let Some(scrambler) = self.scrambler.as_ref() else {
+            return;
+        };
+
+        let mut pending = std::mem::take(&mut *self.pending.lock().unwrap());
+
+        if pending.is_empty() {
+            return;
+        }
+
+        // the data without the status words
+        let mut data = pending
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .flat_map(|response| &response[..response.len().saturating_sub(2)])
+            .copied()
+            .collect::<Vec<_>>();
+
+        scrambler.lock().unwrap().scramble_certificates(&mut data);
+
+        let mut data = data.as_slice();
+
+        for (_, result) in pending.iter_mut() {
+            if let Ok(response) = result {
+                let length = response.len().saturating_sub(2);
+
+                response[..length].copy_from_slice(&data[..length]);
+
+                data = &data[length..];
+            }
+        }
+
+        for (apdu, result) in pending {
+            self.write_transmit(&apdu, result.as_ref().ok().map(hex::encode_upper), result.err());
+        }
+    }
+}
+
+impl Drop for Recorder {
+    #[inline]
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+struct RecordingConnection {
+    inner:    Box<dyn CardConnection>,
+    recorder: Recorder,
+}
+
+impl CardConnection for RecordingConnection {
+    #[inline]
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        self.recorder.transmit(self.inner.as_mut(), apdu)
+    }
+
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        let recorder = &self.recorder;
+
+        self.inner.transaction(&mut |inner| {
+            f(&mut RecordingTransaction {
+                inner,
+                recorder,
+            })
+        })
+    }
+}
+
+struct RecordingTransaction<'a> {
+    inner:    &'a mut dyn CardConnection,
+    recorder: &'a Recorder,
+}
+
+impl CardConnection for RecordingTransaction<'_> {
+    #[inline]
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        self.recorder.transmit(self.inner, apdu)
+    }
+
+    #[inline]
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        f(self);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scramble_basic_data() {
+        let raw =
+            b"000012345678\xA4\xFD\xC6\xA1\xF9\xD6\xFA\x40aaaaaaaaaaa\xF9A1234567890800102M1100505";
+
+        let mut data = raw.to_vec();
+
+        Scrambler::new().scramble_basic_data(&mut data);
+
+        assert!(data[..12].iter().all(u8::is_ascii_digit));
+        assert!(is_common_big5(u16::from_be_bytes([data[12], data[13]])));
+        // the extensions and the user-defined character are kept
+        assert_eq!(raw[14..21], data[14..21]);
+        assert_eq!(raw[20..31], data[20..31]);
+        // the trailing lead byte is replaced with the lead byte of a common name character
+        assert_ne!(raw[31], data[31]);
+        assert!(matches!(data[31], 0xA4..=0xBD));
+        assert_eq!(raw[32..34], data[32..34]);
+        assert!(data[34..42].iter().all(u8::is_ascii_digit));
+        assert_eq!(raw[42..], data[42..]);
+    }
+
+    #[test]
+    fn scramble_certificates() {
+        let name = "王小明".encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<_>>();
+
+        let raw = [
+            &[0x30, 0x00][..],
+            OID_NAME_ATTRIBUTES[0],
+            &[TAG_BMP_STRING, name.len() as u8],
+            &name,
+            OID_NAME_ATTRIBUTES[1],
+            &[0x13, 16],
+            b"0000000112831644",
+            OID_NAME_ATTRIBUTES[0],
+            &[0x0C, 11],
+            "Wang 小明".as_bytes(),
+        ]
+        .concat();
+
+        let mut data = raw.clone();
+
+        Scrambler::new().scramble_certificates(&mut data);
+
+        assert_eq!(raw.len(), data.len());
+        assert_eq!(raw[..7], data[..7]);
+
+        let name = data[9..15]
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        let name = String::from_utf16(&name).unwrap();
+
+        assert!(name.chars().all(|c| NAME_CHARS.contains(c)));
+
+        assert!(data[22..38].iter().all(u8::is_ascii_digit));
+        assert_ne!(raw[22..38], data[22..38]);
+
+        let name = std::str::from_utf8(&data[45..]).unwrap();
+
+        assert_eq!(11, name.len());
+        assert!(name[..4].chars().all(|c| c.is_ascii_alphabetic()));
+        assert!(name[5..].chars().all(|c| NAME_CHARS.contains(c)));
+    }
+
+    /// Returns the data of a file in chunks of 4 bytes.
+    struct FileConnection(Vec<u8>);
+
+    impl CardConnection for FileConnection {
+        fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+            let offset = u16::from_be_bytes([apdu[2], apdu[3]]) as usize;
+            let end = (offset + 4).min(self.0.len());
+
+            Ok([&self.0[offset..end], &[0x90, 0x00]].concat())
+        }
+
+        fn transaction(
+            &mut self,
+            f: &mut dyn FnMut(&mut dyn CardConnection),
+        ) -> Result<(), pcsc::Error> {
+            f(self);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn scramble_certificates_across_chunks() {
+        let path = std::env::temp_dir().join(format!("record-test-{}.jsonl", std::process::id()));
+
+        let serial_number = [OID_NAME_ATTRIBUTES[1], &[0x13, 9], b"123456789"].concat();
+
+        let mut card = FileConnection(serial_number.clone());
+
+        let recorder = Recorder {
+            reader:    String::from("Reader 0"),
+            writer:    Arc::new(Mutex::new(BufWriter::new(File::create(&path).unwrap()))),
+            scrambler: Some(Arc::new(Mutex::new(Scrambler::new()))),
+            pending:   Mutex::new(Vec::new()),
+        };
+
+        for offset in (0..serial_number.len()).step_by(4) {
+            let response =
+                recorder.transmit(&mut card, &[0x00, 0xB0, 0x00, offset as u8, 4]).unwrap();
+
+            // the card data is returned as it is
+            assert_eq!(serial_number[offset..offset + 4], response[..4]);
+        }
+
+        drop(recorder);
+
+        let recorded = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| match serde_json::from_str(line).unwrap() {
+                RecordEntry::Transmit {
+                    response: Some(response), ..
+                } => hex::decode(response).unwrap(),
+                entry => panic!("unexpected {entry:?}"),
+            })
+            .flat_map(|response| response[..response.len() - 2].to_vec())
+            .collect::<Vec<_>>();
+
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(serial_number.len(), recorded.len());
+        assert_eq!(serial_number[..7], recorded[..7]);
+        assert!(recorded[7..].iter().all(u8::is_ascii_digit));
+        assert_ne!(serial_number[7..], recorded[7..]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::Mutex,
+};
+
+use super::{
+    backend::{CardBackend, CardConnection},
+    recorder::{Exchange, RecordEntry},
+};
+
+fn error_from_name(name: &str) -> pcsc::Error {
+    use pcsc::Error::*;
+
+    match name {
+        "Cancelled" => Cancelled,
+        "InvalidHandle" => InvalidHandle,
+        "InvalidParameter" => InvalidParameter,
+        "NoMemory" => NoMemory,
+        "InsufficientBuffer" => InsufficientBuffer,
+        "UnknownReader" => UnknownReader,
+        "Timeout" => Timeout,
+        "SharingViolation" => SharingViolation,
+        "NoSmartcard" => NoSmartcard,
+        "UnknownCard" => UnknownCard,
+        "ProtoMismatch" => ProtoMismatch,
+        "NotReady" => NotReady,
+        "InvalidValue" => InvalidValue,
+        "SystemCancelled" => SystemCancelled,
+        "CommError" => CommError,
+        "InvalidAtr" => InvalidAtr,
+        "NotTransacted" => NotTransacted,
+        "ReaderUnavailable" => ReaderUnavailable,
+        "ReaderUnsupported" => ReaderUnsupported,
+        "CardUnsupported" => CardUnsupported,
+        "NoService" => NoService,
+        "ServiceStopped" => ServiceStopped,
+        "NoReadersAvailable" => NoReadersAvailable,
+        "CommDataLost" => CommDataLost,
+        "ServerTooBusy" => ServerTooBusy,
+        "UnsupportedCard" => UnsupportedCard,
+        "UnresponsiveCard" => UnresponsiveCard,
+        "UnpoweredCard" => UnpoweredCard,
+        "ResetCard" => ResetCard,
+        "RemovedCard" => RemovedCard,
+        "SecurityViolation" => SecurityViolation,
+        "InternalError" => InternalError,
+        _ => UnknownError,
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReaderRecord {
+    connect_error: Option<pcsc::Error>,
+    transmits:     VecDeque<Exchange>,
+}
+
+#[derive(Debug)]
+struct Poll {
+    readers:        Result<Vec<String>, pcsc::Error>,
+    reader_records: HashMap<String, ReaderRecord>,
+}
+
+/// Replays a file written by `RecordingBackend`. Each listing of readers moves to the next recorded poll, and it starts over after the last one.
+pub struct ReplayBackend {
+    polls:   Vec<Poll>,
+    current: Mutex<Option<usize>>,
+}
+
+#[inline]
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl ReplayBackend {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+
+        let mut polls: Vec<Poll> = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line).map_err(invalid_data)? {
+                RecordEntry::Readers {
+                    readers,
+                    error,
+                    ..
+                } => polls.push(Poll {
+                    readers:        match error {
+                        Some(error) => Err(error_from_name(&error)),
+                        None => Ok(readers),
+                    },
+                    reader_records: HashMap::new(),
+                }),
+                RecordEntry::Connect {
+                    reader,
+                    error,
+                } => {
+                    let poll =
+                        polls.last_mut().ok_or_else(|| invalid_data("connect before readers"))?;
+
+                    poll.reader_records.insert(reader, ReaderRecord {
+                        connect_error: error.as_deref().map(error_from_name),
+                        transmits:     VecDeque::new(),
+                    });
+                },
+                RecordEntry::Transmit {
+                    reader,
+                    command,
+                    response,
+                    error,
+                } => {
+                    let poll =
+                        polls.last_mut().ok_or_else(|| invalid_data("transmit before readers"))?;
+
+                    let result = match (response, error) {
+                        (_, Some(error)) => Err(error_from_name(&error)),
+                        (Some(response), None) => Ok(hex::decode(response).map_err(invalid_data)?),
+                        (None, None) => return Err(invalid_data("transmit without a response")),
+                    };
+
+                    poll.reader_records
+                        .entry(reader)
+                        .or_default()
+                        .transmits
+                        .push_back((hex::decode(command).map_err(invalid_data)?, result));
+                },
+            }
+        }
+
+        if polls.is_empty() {
+            return Err(invalid_data("no recorded polls"));
+        }
+
+        Ok(Self {
+            polls,
+            current: Mutex::new(None),
+        })
+    }
+}
+
+impl CardBackend for ReplayBackend {
+    #[inline]
+    fn reestablish(&self) -> Result<(), pcsc::Error> {
+        Ok(())
+    }
+
+    fn list_readers(&self) -> Result<Vec<String>, pcsc::Error> {
+        let mut current = self.current.lock().unwrap();
+
+        let index = current.map(|i| (i + 1) % self.polls.len()).unwrap_or(0);
+
+        *current = Some(index);
+
+        self.polls[index].readers.clone()
+    }
+
+    fn connect(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        let current = *self.current.lock().unwrap();
+
+        let poll = &self.polls[current.ok_or(pcsc::Error::InvalidHandle)?];
+
+        let Some(record) = poll.reader_records.get(reader) else {
+            return Err(pcsc::Error::NoSmartcard);
+        };
+
+        if let Some(error) = record.connect_error {
+            return Err(error);
+        }
+
+        Ok(Box::new(ReplayConnection {
+            transmits: record.transmits.clone()
+        }))
+    }
+}
+
+struct ReplayConnection {
+    transmits: VecDeque<Exchange>,
+}
+
+impl CardConnection for ReplayConnection {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        match self.transmits.iter().position(|(command, _)| command == apdu) {
+            Some(index) => self.transmits.remove(index).unwrap().1,
+            None => {
+                tracing::warn!(target: "card", command = hex::encode_upper(apdu), "not recorded");
+
+                Err(pcsc::Error::CommError)
+            },
+        }
+    }
+
+    #[inline]
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        f(self);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{read_card, CardRecord, ReaderRead};
+
+    #[test]
+    fn replay_recording() {
+        let backend = ReplayBackend::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/recording.jsonl"
+        ))
+        .unwrap();
+
+        assert_eq!(vec!["Reader 0", "Reader 1"], backend.list_readers().unwrap());
+
+        let mut card = backend.connect("Reader 0").unwrap();
+
+        let ReaderRead::Card(card) = read_card(card.as_mut(), "Reader 0") else {
+            panic!("the card is not read");
+        };
+
+        let CardRecord::Nhi(card) = *card else {
+            panic!("not an NHI card");
+        };
+
+        assert_eq!("000012345678", card.card_no);
+        assert_eq!("王小明", card.full_name);
+        assert_eq!("A123456789", card.id_no);
+
+        assert!(matches!(backend.connect("Reader 1"), Err(pcsc::Error::NoSmartcard)));
+
+        assert_eq!(vec!["Reader 0"], backend.list_readers().unwrap());
+        assert!(matches!(backend.connect("Reader 0"), Err(pcsc::Error::NoSmartcard)));
+
+        // starts over after the last poll
+        assert_eq!(vec!["Reader 0", "Reader 1"], backend.list_readers().unwrap());
+    }
+}
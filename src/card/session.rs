@@ -0,0 +1,146 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::prelude::*;
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+use super::CardRecord;
+
+static SESSIONS: Lazy<Mutex<HashMap<String, CardSession>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+/// The event counters of the readers whose cards are not supported or cannot be parsed.
+static UNSUPPORTED: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug)]
+struct CardSession {
+    /// The card type and the card ID.
+    card_id:     (&'static str, String),
+    event_count: Option<u32>,
+    session_id:  String,
+    inserted_at: DateTime<Local>,
+    /// The card read at the last event counter, without the derived fields.
+    card:        Option<CardRecord>,
+}
+
+/// Sets the session of the card in a reader. A new session starts if the card ID or the event counter of the reader changes, so a re-inserted card gets a new session as well.
+pub(super) fn assign(reader: &str, card: &mut CardRecord, event_count: Option<u32>) {
+    UNSUPPORTED.lock().unwrap().remove(reader);
+
+    let mut sessions = SESSIONS.lock().unwrap();
+
+    let card_id = (card.card_type(), String::from(card.card_id()));
+
+    let session = match sessions.get(reader) {
+        Some(session)
+            if session.card_id == card_id
+                && (session.event_count.is_none()
+                    || event_count.is_none()
+                    || session.event_count == event_count) =>
+        {
+            let session = sessions.get_mut(reader).unwrap();
+
+            // the counter may become available after the first read
+            session.event_count = session.event_count.or(event_count);
+
+            session
+        },
+        _ => {
+            let session = CardSession {
+                card_id,
+                event_count,
+                session_id: Uuid::new_v4().to_string(),
+                inserted_at: Local::now(),
+                card: None,
+            };
+
+            tracing::debug!(target: "card", reader, session_id = session.session_id, "new session");
+
+            sessions.insert(String::from(reader), session);
+
+            sessions.get_mut(reader).unwrap()
+        },
+    };
+
+    card.set_session(session.session_id.clone(), session.inserted_at);
+
+    session.card = event_count.map(|_| card.clone());
+}
+
+/// Returns the card read before if the event counter of the reader has not changed since then. Nothing is cached if the backend has no event counter.
+pub(super) fn cached(reader: &str, event_count: Option<u32>) -> Option<CardRecord> {
+    let sessions = SESSIONS.lock().unwrap();
+
+    let session = sessions.get(reader)?;
+
+    if event_count.is_some() && session.event_count == event_count {
+        session.card.clone()
+    } else {
+        None
+    }
+}
+
+/// Whether the card in a reader may still be the one of its session, i.e. the event counter of the reader has not changed, or it is unknown.
+pub(super) fn is_current(reader: &str, event_count: Option<u32>) -> bool {
+    let sessions = SESSIONS.lock().unwrap();
+
+    sessions.get(reader).is_some_and(|session| {
+        session.event_count.is_none() || event_count.is_none() || session.event_count == event_count
+    })
+}
+
+/// Remembers that the card in a reader is not supported, so that it is not read again until the event counter of the reader changes. Nothing is remembered if the backend has no event counter.
+pub(super) fn mark_unsupported(reader: &str, event_count: Option<u32>) {
+    SESSIONS.lock().unwrap().remove(reader);
+
+    let mut unsupported = UNSUPPORTED.lock().unwrap();
+
+    match event_count {
+        Some(event_count) => unsupported.insert(String::from(reader), event_count),
+        None => unsupported.remove(reader),
+    };
+}
+
+/// Whether the card in a reader has been found unsupported and the event counter of the reader has not changed since then.
+#[inline]
+pub(super) fn is_unsupported(reader: &str, event_count: Option<u32>) -> bool {
+    event_count.is_some() && UNSUPPORTED.lock().unwrap().get(reader).copied() == event_count
+}
+
+/// Ends the session of a reader whose card has been removed.
+#[inline]
+pub(super) fn end(reader: &str) {
+    SESSIONS.lock().unwrap().remove(reader);
+    UNSUPPORTED.lock().unwrap().remove(reader);
+}
+
+/// Ends the sessions of the readers which are gone.
+#[inline]
+pub(super) fn retain(readers: &[String]) {
+    SESSIONS.lock().unwrap().retain(|reader, _| readers.contains(reader));
+    UNSUPPORTED.lock().unwrap().retain(|reader, _| readers.contains(reader));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported() {
+        let reader = "Bank Card Reader";
+
+        mark_unsupported(reader, Some(2));
+
+        assert!(is_unsupported(reader, Some(2)));
+        assert!(!is_unsupported(reader, Some(4)));
+        assert!(!is_unsupported(reader, None));
+
+        mark_unsupported(reader, None);
+
+        assert!(!is_unsupported(reader, Some(2)));
+
+        mark_unsupported(reader, Some(2));
+        end(reader);
+
+        assert!(!is_unsupported(reader, Some(2)));
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    CardBackend, CardConnection, NHICardBasic, NHICardEncodeError, Sex, APDU_READ, APDU_SELECT,
+};
+
+const DEFAULT_READER_NAME: &str = "Simulated Reader 0";
+
+const SW_OK: [u8; 2] = [0x90, 0x00];
+const SW_INS_NOT_SUPPORTED: [u8; 2] = [0x6D, 0x00];
+
+#[derive(Debug)]
+pub enum SimulatorError {
+    UnknownReader(String),
+    InvalidCard(NHICardEncodeError),
+}
+
+impl Display for SimulatorError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownReader(name) => f.write_fmt(format_args!("沒有名為 {name} 的模擬讀卡機")),
+            Self::InvalidCard(reason) => {
+                f.write_fmt(format_args!("模擬卡片的資料不正確：{reason}"))
+            },
+        }
+    }
+}
+
+impl Error for SimulatorError {}
+
+impl From<NHICardEncodeError> for SimulatorError {
+    #[inline]
+    fn from(error: NHICardEncodeError) -> Self {
+        Self::InvalidCard(error)
+    }
+}
+
+/// The fields of a fake card. The card number, the sex and the issue date can be omitted.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimulatedCard {
+    #[serde(default)]
+    pub reader:     Option<String>,
+    #[serde(default)]
+    pub card_no:    Option<String>,
+    pub full_name:  String,
+    pub id_no:      String,
+    pub birth_date: NaiveDate,
+    #[serde(default)]
+    pub sex:        Option<Sex>,
+    #[serde(default)]
+    pub issue_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SimulatorConfig {
+    #[serde(default)]
+    readers: Vec<String>,
+    #[serde(default)]
+    cards:   Vec<SimulatedCard>,
+}
+
+#[derive(Debug)]
+struct SimulatedReader {
+    name:        String,
+    /// The response of the read command.
+    card:        Option<Vec<u8>>,
+    event_count: u32,
+}
+
+/// Simulated readers whose cards are inserted and removed via the HTTP API instead of by hand.
+#[derive(Debug)]
+pub struct Simulator {
+    readers:      Mutex<Vec<SimulatedReader>>,
+    next_card_no: Mutex<u64>,
+}
+
+impl Simulator {
+    /// Loads the readers and the initially inserted cards from a TOML file. Without the file, there is one empty reader.
+    pub fn load<P: AsRef<Path>>(path: Option<P>) -> anyhow::Result<Arc<Self>> {
+        let config: SimulatorConfig = match path {
+            Some(path) => toml::from_str(&fs::read_to_string(path)?)?,
+            None => SimulatorConfig::default(),
+        };
+
+        let mut readers: Vec<SimulatedReader> = Vec::new();
+
+        for name in config
+            .readers
+            .into_iter()
+            .chain(config.cards.iter().filter_map(|card| card.reader.clone()))
+        {
+            if !readers.iter().any(|reader| reader.name == name) {
+                readers.push(SimulatedReader {
+                    name,
+                    card: None,
+                    event_count: 0,
+                });
+            }
+        }
+
+        if readers.is_empty() {
+            readers.push(SimulatedReader {
+                name:        String::from(DEFAULT_READER_NAME),
+                card:        None,
+                event_count: 0,
+            });
+        }
+
+        let simulator = Arc::new(Self {
+            readers:      Mutex::new(readers),
+            next_card_no: Mutex::new(1),
+        });
+
+        for card in config.cards {
+            simulator.insert(card)?;
+        }
+
+        Ok(simulator)
+    }
+
+    #[inline]
+    pub fn backend(self: &Arc<Self>) -> SimulatedBackend {
+        SimulatedBackend {
+            simulator: self.clone()
+        }
+    }
+
+    fn encode(&self, card: SimulatedCard) -> Result<Vec<u8>, SimulatorError> {
+        let card_no = match card.card_no {
+            Some(card_no) => card_no,
+            None => {
+                let mut next_card_no = self.next_card_no.lock().unwrap();
+
+                let card_no = format!("{:012}", *next_card_no);
+
+                *next_card_no += 1;
+
+                card_no
+            },
+        };
+
+        let sex = card.sex.unwrap_or(match card.id_no.as_bytes().get(1) {
+            Some(b'2') => Sex::Female,
+            _ => Sex::Male,
+        });
+
+        let mut data = NHICardBasic::builder()
+            .card_no(card_no)
+            .full_name(card.full_name)
+            .id_no(card.id_no)
+            .birth_date(card.birth_date)
+            .sex(sex)
+            .issue_date(card.issue_date.unwrap_or_else(|| Local::now().date_naive()))
+            .build_raw()?
+            .to_vec();
+
+        data.extend_from_slice(&SW_OK);
+
+        Ok(data)
+    }
+
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.readers.lock().unwrap().len()
+    }
+
+    /// Inserts a card into the given reader, or the first reader. The card which is already in the reader is replaced.
+    pub fn insert(&self, card: SimulatedCard) -> Result<(), SimulatorError> {
+        let reader_name = card.reader.clone();
+        let data = self.encode(card)?;
+
+        let mut readers = self.readers.lock().unwrap();
+
+        let reader = match reader_name {
+            Some(name) => readers
+                .iter_mut()
+                .find(|reader| reader.name == name)
+                .ok_or(SimulatorError::UnknownReader(name))?,
+            None => &mut readers[0],
+        };
+
+        tracing::info!(target: "simulator", reader = reader.name, "inserted");
+
+        reader.card = Some(data);
+        reader.event_count += 1;
+
+        Ok(())
+    }
+
+    /// Removes the card from the given reader, or the first reader.
+    pub fn remove(&self, reader_name: Option<&str>) -> Result<(), SimulatorError> {
+        let mut readers = self.readers.lock().unwrap();
+
+        let reader = match reader_name {
+            Some(name) => readers
+                .iter_mut()
+                .find(|reader| reader.name == name)
+                .ok_or_else(|| SimulatorError::UnknownReader(String::from(name)))?,
+            None => &mut readers[0],
+        };
+
+        tracing::info!(target: "simulator", reader = reader.name, "removed");
+
+        if reader.card.take().is_some() {
+            reader.event_count += 1;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SimulatedBackend {
+    simulator: Arc<Simulator>,
+}
+
+impl CardBackend for SimulatedBackend {
+    #[inline]
+    fn reestablish(&self) -> Result<(), pcsc::Error> {
+        Ok(())
+    }
+
+    fn list_readers(&self) -> Result<Vec<String>, pcsc::Error> {
+        Ok(self
+            .simulator
+            .readers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|reader| reader.name.clone())
+            .collect())
+    }
+
+    fn card_event_count(&self, reader: &str) -> Option<u32> {
+        self.simulator
+            .readers
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.name == reader)
+            .map(|r| r.event_count)
+    }
+
+    fn connect(&self, reader: &str) -> Result<Box<dyn CardConnection>, pcsc::Error> {
+        let readers = self.simulator.readers.lock().unwrap();
+
+        match readers.iter().find(|r| r.name == reader) {
+            Some(SimulatedReader {
+                card: Some(data), ..
+            }) => Ok(Box::new(SimulatedConnection {
+                data: data.clone()
+            })),
+            Some(_) => Err(pcsc::Error::NoSmartcard),
+            None => Err(pcsc::Error::UnknownReader),
+        }
+    }
+}
+
+pub struct SimulatedConnection {
+    data: Vec<u8>,
+}
+
+impl CardConnection for SimulatedConnection {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, pcsc::Error> {
+        Ok(if apdu == APDU_SELECT {
+            SW_OK.to_vec()
+        } else if apdu == APDU_READ {
+            self.data.clone()
+        } else {
+            SW_INS_NOT_SUPPORTED.to_vec()
+        })
+    }
+
+    #[inline]
+    fn transaction(
+        &mut self,
+        f: &mut dyn FnMut(&mut dyn CardConnection),
+    ) -> Result<(), pcsc::Error> {
+        f(self);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{read_card, CardRecord, ReaderRead};
+
+    fn card(reader: Option<&str>) -> SimulatedCard {
+        SimulatedCard {
+            reader:     reader.map(String::from),
+            card_no:    None,
+            full_name:  String::from("王小美"),
+            id_no:      String::from("A223456789"),
+            birth_date: NaiveDate::from_ymd_opt(1990, 1, 2).unwrap(),
+            sex:        None,
+            issue_date: NaiveDate::from_ymd_opt(2020, 3, 4),
+        }
+    }
+
+    fn read(backend: &SimulatedBackend, reader: &str) -> NHICardBasic {
+        let mut connection = backend.connect(reader).unwrap();
+
+        let ReaderRead::Card(card) = read_card(connection.as_mut(), reader) else {
+            panic!("the card is not read");
+        };
+
+        let CardRecord::Nhi(card) = *card else {
+            panic!("not an NHI card");
+        };
+
+        *card
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let simulator = Simulator::load(None::<&str>).unwrap();
+        let backend = simulator.backend();
+
+        assert_eq!(vec![DEFAULT_READER_NAME], backend.list_readers().unwrap());
+        assert_eq!(Some(0), backend.card_event_count(DEFAULT_READER_NAME));
+        assert!(matches!(backend.connect(DEFAULT_READER_NAME), Err(pcsc::Error::NoSmartcard)));
+
+        simulator.insert(card(None)).unwrap();
+
+        assert_eq!(Some(1), backend.card_event_count(DEFAULT_READER_NAME));
+
+        let nhi_card = read(&backend, DEFAULT_READER_NAME);
+
+        assert_eq!("000000000001", nhi_card.card_no);
+        assert_eq!("王小美", nhi_card.full_name);
+        assert_eq!(Sex::Female, nhi_card.sex);
+
+        // replacing the card gets a new card number
+        simulator.insert(card(Some(DEFAULT_READER_NAME))).unwrap();
+
+        assert_eq!(Some(2), backend.card_event_count(DEFAULT_READER_NAME));
+        assert_eq!("000000000002", read(&backend, DEFAULT_READER_NAME).card_no);
+
+        simulator.remove(None).unwrap();
+
+        assert_eq!(Some(3), backend.card_event_count(DEFAULT_READER_NAME));
+        assert!(matches!(backend.connect(DEFAULT_READER_NAME), Err(pcsc::Error::NoSmartcard)));
+
+        // removing from an empty reader is not an event
+        simulator.remove(Some(DEFAULT_READER_NAME)).unwrap();
+
+        assert_eq!(Some(3), backend.card_event_count(DEFAULT_READER_NAME));
+    }
+
+    #[test]
+    fn unknown_reader() {
+        let simulator = Simulator::load(None::<&str>).unwrap();
+        let backend = simulator.backend();
+
+        assert!(matches!(
+            simulator.insert(card(Some("Unknown Reader"))),
+            Err(SimulatorError::UnknownReader(name)) if name == "Unknown Reader"
+        ));
+        assert!(matches!(
+            simulator.remove(Some("Unknown Reader")),
+            Err(SimulatorError::UnknownReader(_))
+        ));
+
+        assert_eq!(Some(0), backend.card_event_count(DEFAULT_READER_NAME));
+        assert_eq!(None, backend.card_event_count("Unknown Reader"));
+        assert!(matches!(backend.connect("Unknown Reader"), Err(pcsc::Error::UnknownReader)));
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Mutex;
+
+use chrono::prelude::*;
+use once_cell::sync::Lazy;
+use pcsc::{Context, Scope};
+use serde::Serialize;
+use tokio::{sync::watch, task};
+
+static STATUS: Lazy<Mutex<CardSubsystemStatus>> =
+    Lazy::new(|| Mutex::new(CardSubsystemStatus::default()));
+static READER_NAMES: Lazy<watch::Sender<Option<Vec<String>>>> =
+    Lazy::new(|| watch::channel(None).0);
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CardSubsystemStatus {
+    pub context_established: bool,
+    pub reader_names:        Option<Vec<String>>,
+    pub last_success_time:   Option<DateTime<Local>>,
+    pub last_error:          Option<String>,
+    pub timed_out_readers:   Vec<String>,
+    pub busy_readers:        Vec<String>,
+}
+
+#[inline]
+pub fn card_subsystem_status() -> CardSubsystemStatus {
+    STATUS.lock().unwrap().clone()
+}
+
+/// Notifies the receiver whenever the readers are listed with different names than the last time.
+#[inline]
+pub fn subscribe_reader_names() -> watch::Receiver<Option<Vec<String>>> {
+    READER_NAMES.subscribe()
+}
+
+#[inline]
+pub(super) fn update_status<F: FnOnce(&mut CardSubsystemStatus)>(f: F) {
+    f(&mut STATUS.lock().unwrap())
+}
+
+pub(super) fn record_success(
+    reader_names: Vec<String>,
+    timed_out_readers: Vec<String>,
+    busy_readers: Vec<String>,
+) {
+    READER_NAMES.send_if_modified(|names| {
+        if names.as_ref() == Some(&reader_names) {
+            return false;
+        }
+
+        *names = Some(reader_names.clone());
+
+        true
+    });
+
+    update_status(|status| {
+        status.context_established = true;
+        status.reader_names = Some(reader_names);
+        status.timed_out_readers = timed_out_readers;
+        status.busy_readers = busy_readers;
+        status.last_success_time = Some(Local::now());
+    });
+}
+
+pub(super) fn record_error(error: pcsc::Error) {
+    update_status(|status| {
+        if matches!(
+            error,
+            pcsc::Error::NoService | pcsc::Error::ServiceStopped | pcsc::Error::InvalidHandle
+        ) {
+            status.context_established = false;
+        }
+
+        status.last_error = Some(error.to_string());
+    });
+}
+
+/// Checks the PC/SC service with a separate context so that it does not wait for an ongoing card read. Returns the number of readers.
+pub async fn probe_pcsc() -> Result<usize, pcsc::Error> {
+    task::spawn_blocking(|| {
+        let context = Context::establish(Scope::User)?;
+
+        match context.list_readers_owned() {
+            Ok(readers) => Ok(readers.len()),
+            Err(pcsc::Error::NoReadersAvailable) => Ok(0),
+            Err(error) => Err(error),
+        }
+    })
+    .await
+    .unwrap()
+}
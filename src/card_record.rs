@@ -0,0 +1,81 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{MOICACard, NHICardBasic};
+
+/// A card of any supported type, tagged with `card_type` in JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "card_type", rename_all = "snake_case")]
+pub enum CardRecord {
+    Nhi(Box<NHICardBasic>),
+    Moica(MOICACard),
+}
+
+impl CardRecord {
+    /// The same as the `card_type` in JSON.
+    #[inline]
+    pub const fn card_type(&self) -> &'static str {
+        match self {
+            Self::Nhi(_) => "nhi",
+            Self::Moica(_) => "moica",
+        }
+    }
+
+    /// Identifies the card among the cards of the same type, e.g. the card number of an NHI card.
+    #[inline]
+    pub fn card_id(&self) -> &str {
+        match self {
+            Self::Nhi(card) => &card.card_no,
+            Self::Moica(card) => card.card_id(),
+        }
+    }
+
+    #[inline]
+    pub fn reader_name(&self) -> Option<&str> {
+        match self {
+            Self::Nhi(card) => card.reader_name.as_deref(),
+            Self::Moica(card) => card.reader_name.as_deref(),
+        }
+    }
+
+    #[inline]
+    pub fn set_reader_name(&mut self, reader_name: String) {
+        match self {
+            Self::Nhi(card) => card.reader_name = Some(reader_name),
+            Self::Moica(card) => card.reader_name = Some(reader_name),
+        }
+    }
+
+    #[inline]
+    pub fn set_session(&mut self, session_id: String, inserted_at: DateTime<Local>) {
+        let (card_session_id, card_inserted_at) = match self {
+            Self::Nhi(card) => (&mut card.session_id, &mut card.inserted_at),
+            Self::Moica(card) => (&mut card.session_id, &mut card.inserted_at),
+        };
+
+        *card_session_id = Some(session_id);
+        *card_inserted_at = Some(inserted_at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tagged_serde_round_trip() {
+        let card = NHICardBasic::builder().build().unwrap().with_derived();
+
+        let json = serde_json::to_value(CardRecord::Nhi(Box::new(card.clone()))).unwrap();
+
+        assert_eq!("nhi", json["card_type"]);
+        assert_eq!(card.card_no, json["card_no"]);
+
+        let CardRecord::Nhi(deserialized) = serde_json::from_value(json).unwrap() else {
+            panic!("not an NHI card");
+        };
+
+        assert_eq!(card.card_no, deserialized.card_no);
+        assert_eq!(card.derived, deserialized.derived);
+    }
+}
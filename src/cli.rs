@@ -1,12 +1,17 @@
 use std::{
     net::{AddrParseError, IpAddr},
+    path::PathBuf,
     str::FromStr,
 };
 
-use clap::{CommandFactory, FromArgMatches, Parser};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use concat_with::concat_line;
 use terminal_size::terminal_size;
 
+use crate::{
+    audit::AuditLogFormat, card::ApduPattern, server::MAX_LEASE_TTL_SECS, template::CardTemplate,
+};
+
 const APP_NAME: &str = "TW NHI IC Card Service";
 const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 const CARGO_PKG_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
@@ -16,8 +21,9 @@ const AFTER_HELP: &str = "Enjoy it! https://magiclen.org";
 const APP_ABOUT: &str = concat!(
     "透過 HTTP API 讀取中華民國健保卡。\n\nEXAMPLES:\n",
     concat_line!(prefix "tw-nhi-icc-service ",
-        "                      # 啟動 HTTP 服務，監聽 127.0.0.1:58113",
-        "-i 0.0.0.0 -p 12345   # 啟動 HTTP 服務，監聽 0.0.0.0:12345",
+        "                              # 啟動 HTTP 服務，監聽 127.0.0.1:58113",
+        "-i 0.0.0.0 -p 12345           # 啟動 HTTP 服務，監聽 0.0.0.0:12345",
+        "simulate --cards cards.toml   # 以模擬的讀卡機與卡片啟動 HTTP 服務",
     )
 );
 
@@ -28,6 +34,9 @@ const APP_ABOUT: &str = concat!(
 #[command(author = CARGO_PKG_AUTHORS)]
 #[command(after_help = AFTER_HELP)]
 pub struct CLIArgs {
+    #[command(subcommand)]
+    pub command: Option<CLICommand>,
+
     #[arg(short, long, visible_alias = "ip")]
     #[arg(value_parser = parse_ip_addr)]
     #[arg(default_value = "127.0.0.1")]
@@ -43,6 +52,205 @@ pub struct CLIArgs {
     #[arg(default_value = "3")]
     #[arg(help = "WebSocket 回傳卡片資料的預設時間間隔（秒）")]
     pub default_ws_card_fetch_interval: u64,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(default_value = "25")]
+    #[arg(help = "WebSocket 傳送 ping 的時間間隔（秒）")]
+    pub ws_ping_interval: u64,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(default_value = "10")]
+    #[arg(help = "WebSocket 等待客戶端回應的額外逾時時間（秒）")]
+    pub ws_pong_timeout: u64,
+
+    #[arg(long, value_name = "COUNT")]
+    #[arg(default_value = "0")]
+    #[arg(help = "WebSocket 同時連線數的上限，0 表示不限制")]
+    pub ws_max_connections: usize,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..=MAX_LEASE_TTL_SECS))]
+    #[arg(default_value = "30")]
+    #[arg(help = "保留讀卡機的有效時間（秒），最長 86400 秒，客戶端須在到期前續約")]
+    pub lease_ttl: u64,
+
+    #[arg(long, value_name = "TOKEN")]
+    #[arg(help = "管理端點（例如 /audit）所需的 Bearer token，未設定則停用管理端點")]
+    pub admin_token: Option<String>,
+
+    #[arg(long = "apdu-allow", value_name = "CLA:INS", requires = "admin_token")]
+    #[arg(value_parser = ApduPattern::parse)]
+    #[arg(help = "啟用 POST /readers/{name}/apdu 並允許傳送 CLA 與 INS 符合的 APDU，例如 \
+                  \"00:B0\"，* 表示任意值，可以重複使用此參數，須一併設定 --admin-token")]
+    pub apdu_allowlist: Vec<ApduPattern>,
+
+    #[arg(long, value_name = "FILE", requires = "audit_hash_key")]
+    #[arg(help = "稽核紀錄檔的路徑，未設定則不記錄，須一併設定 --audit-hash-key")]
+    pub audit_log: Option<PathBuf>,
+
+    #[arg(long, value_name = "KEY")]
+    #[arg(help = "稽核紀錄以 HMAC-SHA256 雜湊卡號與 API key \
+                  時使用的密鑰，更換密鑰後新舊紀錄的雜湊值將無法比對")]
+    pub audit_hash_key: Option<String>,
+
+    #[arg(long, value_name = "FORMAT")]
+    #[arg(default_value = "json-lines")]
+    #[arg(help = "稽核紀錄檔的格式")]
+    pub audit_log_format: AuditLogFormat,
+
+    #[arg(long, value_name = "DAYS")]
+    #[arg(help = "稽核紀錄的保存天數，未設定則永久保存")]
+    pub audit_retention_days: Option<u32>,
+
+    #[arg(long = "webhook-url", value_name = "URL")]
+    #[arg(help = "插入或移除健保卡時要 POST 事件的 URL，可以重複使用此參數設定多個 URL")]
+    pub webhook_urls: Vec<String>,
+
+    #[arg(long, value_name = "SECRET")]
+    #[arg(help = "用來以 HMAC-SHA256 簽署 webhook 請求內容的密鑰")]
+    pub webhook_secret: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "遮蔽 webhook 事件中的卡號、姓名與身份證字號")]
+    pub webhook_mask: bool,
+
+    #[arg(long, value_name = "COUNT")]
+    #[arg(default_value = "5")]
+    #[arg(help = "webhook 傳送失敗時的重試次數")]
+    pub webhook_max_retries: u32,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(help = "記錄傳送失敗的 webhook 事件的檔案路徑")]
+    pub webhook_dead_letter: Option<PathBuf>,
+
+    #[arg(long, value_name = "HOST")]
+    #[arg(help = "要發布卡片事件的 MQTT broker 主機，未設定則不使用 MQTT")]
+    pub mqtt_host: Option<String>,
+
+    #[arg(long, value_name = "PORT")]
+    #[arg(default_value = "1883")]
+    #[arg(help = "MQTT broker 的連接埠")]
+    pub mqtt_port: u16,
+
+    #[arg(long)]
+    #[arg(help = "使用 TLS 連線到 MQTT broker")]
+    pub mqtt_tls: bool,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(help = "用來驗證 MQTT broker 的 CA 憑證（PEM），未設定則使用系統的憑證")]
+    pub mqtt_ca_file: Option<PathBuf>,
+
+    #[arg(long, value_name = "USERNAME")]
+    #[arg(help = "MQTT 的使用者名稱")]
+    pub mqtt_username: Option<String>,
+
+    #[arg(long, value_name = "PASSWORD")]
+    #[arg(help = "MQTT 的密碼")]
+    pub mqtt_password: Option<String>,
+
+    #[arg(long, value_name = "ID")]
+    #[arg(default_value = "tw-nhi-icc-service")]
+    #[arg(help = "MQTT 的 client ID，同一個 broker 上的每個服務須使用不同的 ID")]
+    pub mqtt_client_id: String,
+
+    #[arg(long, value_name = "PREFIX")]
+    #[arg(default_value = "tw-nhi-icc")]
+    #[arg(help = "MQTT 主題的前綴")]
+    pub mqtt_topic_prefix: String,
+
+    #[arg(long, value_name = "QOS")]
+    #[arg(value_parser = clap::value_parser!(u8).range(0..=2))]
+    #[arg(default_value = "1")]
+    #[arg(help = "MQTT 發布訊息的 QoS")]
+    pub mqtt_qos: u8,
+
+    #[arg(long)]
+    #[arg(help = "遮蔽 MQTT 訊息中的卡號、姓名與身份證字號")]
+    pub mqtt_mask: bool,
+
+    #[arg(long, value_name = "HOST:PORT")]
+    #[arg(help = "插入健保卡時，透過 MLLP 傳送 HL7 v2 ADT^A04 訊息的目的地")]
+    pub hl7_mllp: Option<String>,
+
+    #[arg(long, value_name = "TEMPLATE")]
+    #[arg(value_parser = CardTemplate::parse)]
+    #[arg(help = "插入健保卡時，透過虛擬鍵盤（Linux 的 uinput 或 Windows 的 \
+                  SendInput）輸入的模板，例如 \"{id_no}\\t{full_name}\\n\"，未設定則不啟用")]
+    pub keyboard_wedge_template: Option<CardTemplate>,
+
+    #[arg(long, value_name = "MILLI_SECONDS")]
+    #[arg(default_value = "10")]
+    #[arg(help = "虛擬鍵盤每次按鍵的間隔（毫秒）")]
+    pub keyboard_wedge_key_delay: u64,
+
+    #[arg(long, value_name = "TEMPLATE")]
+    #[arg(value_parser = CardTemplate::parse)]
+    #[arg(help = "插入健保卡時，複製到剪貼簿的模板，例如 \"{id_no}\"，未設定則不啟用")]
+    pub clipboard_template: Option<CardTemplate>,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(default_value = "30")]
+    #[arg(help = "複製到剪貼簿的內容在幾秒後自動清除，0 表示不清除")]
+    pub clipboard_clear_after: u64,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(default_value = "10")]
+    #[arg(help = "每台讀卡機讀取的逾時時間（秒），逾時的讀卡機會被略過，不影響其它讀卡機")]
+    pub reader_timeout: u64,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    #[arg(default_value = "5")]
+    #[arg(help = "背景輪詢讀卡機的最長間隔（秒），讀卡機或卡片有變化時會立即讀取")]
+    pub poll_interval: u64,
+
+    #[arg(long)]
+    #[arg(help = "與其它 PC/SC 應用程式共用讀卡機：以交易讀取卡片，\
+                  讀卡機被占用時暫停讀取並逐漸延長等待時間")]
+    pub cooperative: bool,
+
+    #[arg(long, value_name = "SECONDS")]
+    #[arg(default_value = "3")]
+    #[arg(help = "啟用 --cooperative 時，其它應用程式使用卡片後，須等待幾秒才會再讀取該讀卡機")]
+    pub quiet_period: u64,
+
+    #[arg(long)]
+    #[arg(help = "在卡片資料中加入年齡、民國年格式的日期與卡片的使用時間等衍生欄位")]
+    pub derived_fields: bool,
+
+    #[arg(long)]
+    #[arg(help = "姓名中有無法對應到 Unicode 的字元時，以 \u{FFFD} 替代，而不是視為無法讀取的卡片")]
+    pub lenient_names: bool,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(help = "Big5 造字區等擴充字與 Unicode 的對應表，每行為一組以空白分隔的 Big5 碼與 \
+                  Unicode 碼位，例如 \"FA40 U+2420E\"，會優先於內建的造字區對應")]
+    pub big5_extension_map: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE")]
+    #[arg(help = "將每一台讀卡機的 APDU 指令與回應記錄到檔案中")]
+    pub record: Option<PathBuf>,
+
+    #[arg(long, requires = "record")]
+    #[arg(help = "記錄時將卡號、姓名與身份證字號替換為隨機的資料")]
+    pub record_scramble: bool,
+
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    #[arg(help = "不使用 PC/SC，改為重播以 --record 記錄的檔案")]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CLICommand {
+    #[command(about = "不使用實體的讀卡機，以模擬的讀卡機與卡片啟動 HTTP 服務，可透過 POST \
+                       /sim/insert 與 POST /sim/remove 插入與移除卡片")]
+    Simulate {
+        #[arg(long, value_name = "FILE")]
+        #[arg(help = "設定模擬讀卡機與一開始就插入的卡片的 TOML 檔案")]
+        cards: Option<PathBuf>,
+    },
 }
 
 #[inline]
@@ -0,0 +1,246 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use futures::{Stream, StreamExt};
+use rustls::{crypto::ring, ClientConfig, RootCertStore};
+use serde::de::DeserializeOwned;
+use tokio_tungstenite::{
+    tungstenite::{self, Message},
+    Connector,
+};
+
+use crate::{CardRecord, ErrorResponse, NHICardBasic, ServiceVersion};
+
+#[derive(Debug)]
+pub enum ClientError {
+    Http(reqwest::Error),
+    /// The service responds with an error status, along with the message if there is one.
+    Status(u16, Option<String>),
+    WebSocket(tungstenite::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for ClientError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(error) => Display::fmt(error, f),
+            Self::Status(status, Some(message)) => {
+                f.write_fmt(format_args!("服務回應了 {status}：{message}"))
+            },
+            Self::Status(status, None) => f.write_fmt(format_args!("服務回應了 {status}")),
+            Self::WebSocket(error) => Display::fmt(error, f),
+            Self::Json(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    #[inline]
+    fn from(error: reqwest::Error) -> Self {
+        Self::Http(error)
+    }
+}
+
+impl From<tungstenite::Error> for ClientError {
+    #[inline]
+    fn from(error: tungstenite::Error) -> Self {
+        Self::WebSocket(error)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    #[inline]
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+/// Uses the ring provider explicitly, since other crates in the same process may enable more than one rustls provider, in which case rustls cannot choose a default one.
+fn tls_connector() -> Connector {
+    let root_store = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+
+    Connector::Rustls(Arc::new(config))
+}
+
+/// A client of the HTTP API of this service.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http:     reqwest::Client,
+}
+
+impl Client {
+    /// Creates a client of the service at `base_url`, e.g. `http://127.0.0.1:8000`.
+    #[inline]
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        let mut base_url = base_url.into();
+
+        while base_url.ends_with('/') {
+            base_url.pop();
+        }
+
+        Self {
+            base_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        let response = self.http.get(format!("{}{path}", self.base_url)).send().await?;
+
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        if !status.is_success() {
+            let message =
+                serde_json::from_slice::<ErrorResponse>(&body).ok().map(|response| response.error);
+
+            return Err(ClientError::Status(status.as_u16(), message));
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Reads the cards in all readers via `GET /`.
+    #[inline]
+    pub async fn read_cards(&self) -> Result<Vec<NHICardBasic>, ClientError> {
+        self.get("/").await
+    }
+
+    /// Reads the cards of all types in all readers via `GET /cards`.
+    #[inline]
+    pub async fn read_all_cards(&self) -> Result<Vec<CardRecord>, ClientError> {
+        self.get("/cards").await
+    }
+
+    #[inline]
+    pub async fn version(&self) -> Result<ServiceVersion, ClientError> {
+        self.get("/version").await
+    }
+
+    fn ws_url(&self, interval: Option<u64>) -> String {
+        let mut url = match self.base_url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{rest}/ws"),
+            Some((_, rest)) => format!("ws://{rest}/ws"),
+            None => format!("ws://{}/ws", self.base_url),
+        };
+
+        if let Some(interval) = interval {
+            url.push_str(&format!("?interval={interval}"));
+        }
+
+        url
+    }
+
+    /// Connects to `GET /ws` and returns a stream of the cards in all readers, which are sent every `interval` seconds, or the default interval of the service. The stream ends when the connection is closed.
+    pub async fn subscribe(
+        &self,
+        interval: Option<u64>,
+    ) -> Result<impl Stream<Item = Result<Vec<NHICardBasic>, ClientError>>, ClientError> {
+        let url = self.ws_url(interval);
+
+        let connector = url.starts_with("wss://").then(tls_connector);
+
+        let (socket, _) =
+            tokio_tungstenite::connect_async_tls_with_config(url, None, false, connector).await?;
+
+        // pings are answered while the stream is being polled
+        Ok(socket
+            .take_while(|message| futures::future::ready(!matches!(message, Ok(Message::Close(_)))))
+            .filter_map(|message| async move {
+                match message {
+                    Ok(Message::Text(text)) => {
+                        Some(serde_json::from_str(&text).map_err(Into::into))
+                    },
+                    Ok(_) => None,
+                    Err(error) => Some(Err(error.into())),
+                }
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, routing::get, Json, Router};
+    use tokio::net::TcpListener;
+    use tungstenite::error::UrlError;
+
+    use super::*;
+
+    async fn serve(router: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        format!("http://{address}/")
+    }
+
+    #[test]
+    fn ws_url() {
+        assert_eq!("ws://127.0.0.1:8000/ws", Client::new("http://127.0.0.1:8000/").ws_url(None));
+        assert_eq!(
+            "wss://example.com/nhi/ws?interval=3",
+            Client::new("https://example.com/nhi").ws_url(Some(3))
+        );
+        assert_eq!("ws://127.0.0.1:8000/ws", Client::new("127.0.0.1:8000").ws_url(None));
+    }
+
+    #[tokio::test]
+    async fn read_cards() {
+        let card = NHICardBasic::builder().build().unwrap();
+
+        let router =
+            Router::new().route("/", get(move || async move { Json(vec![card.clone()]) })).route(
+                "/version",
+                get(|| async {
+                    (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        Json(ErrorResponse {
+                            error: String::from("維護中")
+                        }),
+                    )
+                }),
+            );
+
+        let client = Client::new(serve(router).await);
+
+        let cards = client.read_cards().await.unwrap();
+
+        assert_eq!(1, cards.len());
+        assert_eq!("A123456789", cards[0].id_no);
+
+        assert!(matches!(
+            client.version().await,
+            Err(ClientError::Status(503, Some(message))) if message == "維護中"
+        ));
+    }
+
+    #[tokio::test]
+    async fn subscribe_over_tls() {
+        // a plain HTTP server, so the TLS handshake is attempted and fails
+        let base_url = serve(Router::new()).await.replacen("http://", "https://", 1);
+
+        let Err(error) = Client::new(base_url).subscribe(None).await else {
+            panic!("the TLS handshake should fail");
+        };
+
+        assert!(!matches!(
+            error,
+            ClientError::WebSocket(tungstenite::Error::Url(UrlError::TlsFeatureNotEnabled))
+        ));
+        assert!(matches!(error, ClientError::WebSocket(_)));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use arboard::Clipboard;
+use tokio::{sync::broadcast::error::RecvError, task};
+
+use crate::{
+    card::{subscribe_card_events, CardEventKind},
+    server::is_reader_reserved,
+    template::CardTemplate,
+};
+
+/// Copies a templated string to the system clipboard whenever a card is inserted.
+pub struct ClipboardCopier {
+    template: CardTemplate,
+    sender:   mpsc::Sender<String>,
+}
+
+/// Owns the clipboard on its own thread, since some platforms serve the clipboard content from the thread which set it.
+fn clipboard_thread(
+    mut clipboard: Clipboard,
+    receiver: mpsc::Receiver<String>,
+    clear_after: Option<Duration>,
+) {
+    let mut pending_clear: Option<(Instant, String)> = None;
+
+    loop {
+        let received = match pending_clear.as_ref() {
+            Some((deadline, _)) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            },
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(text) => match clipboard.set_text(&text) {
+                Ok(_) => {
+                    tracing::debug!(target: "clipboard", "copied");
+
+                    pending_clear = clear_after.map(|d| (Instant::now() + d, text));
+                },
+                Err(error) => tracing::error!(target: "clipboard", ?error),
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                let (_, text) = pending_clear.take().unwrap();
+
+                // do not clear what the user has copied afterwards
+                if clipboard.get_text().map(|current| current == text).unwrap_or(false) {
+                    match clipboard.clear() {
+                        Ok(_) => tracing::debug!(target: "clipboard", "cleared"),
+                        Err(error) => tracing::error!(target: "clipboard", ?error),
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+impl ClipboardCopier {
+    pub fn new(template: CardTemplate, clear_after: Option<Duration>) -> anyhow::Result<Self> {
+        let clipboard =
+            Clipboard::new().map_err(|error| anyhow::anyhow!("無法存取剪貼簿：{error}"))?;
+
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || clipboard_thread(clipboard, receiver, clear_after));
+
+        Ok(Self {
+            template,
+            sender,
+        })
+    }
+
+    pub fn spawn_background_tasks(self) {
+        task::spawn(async move {
+            let mut receiver = subscribe_card_events();
+
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if is_reader_reserved(&event.reader_name) => (),
+                    Ok(event) if event.kind == CardEventKind::Inserted => {
+                        if self.sender.send(self.template.render(&event.card)).is_err() {
+                            break;
+                        }
+                    },
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(count)) => {
+                        tracing::warn!(target: "clipboard", count, "card events lagged");
+                    },
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,76 @@
+/// A DER element. Only the single-byte tags are supported, which is enough for PKCS #15 directories and X.509 certificates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Tlv<'a> {
+    pub(crate) tag:   u8,
+    pub(crate) value: &'a [u8],
+}
+
+/// Returns the length of the header and the length of the value.
+pub(crate) fn read_header(data: &[u8]) -> Option<(usize, usize)> {
+    let first = *data.get(1)?;
+
+    if first < 0x80 {
+        return Some((2, first as usize));
+    }
+
+    let count = (first & 0x7F) as usize;
+
+    if count == 0 || count > 4 {
+        return None;
+    }
+
+    let length =
+        data.get(2..2 + count)?.iter().fold(0usize, |length, &b| (length << 8) | b as usize);
+
+    Some((2 + count, length))
+}
+
+/// Reads a TLV and returns it along with the remaining data. Returns `None` if the TLV is malformed or truncated.
+pub(crate) fn read_tlv(data: &[u8]) -> Option<(Tlv<'_>, &[u8])> {
+    let (header_length, length) = read_header(data)?;
+
+    // the length is untrusted and can be up to 32 bits
+    let end = header_length.checked_add(length)?;
+
+    let value = data.get(header_length..end)?;
+
+    Some((
+        Tlv {
+            tag: data[0],
+            value,
+        },
+        &data[end..],
+    ))
+}
+
+/// Iterates the TLVs in a constructed value. Padding of `00` or `FF` bytes ends the iteration.
+pub(crate) fn children(mut data: &[u8]) -> impl Iterator<Item = Tlv<'_>> {
+    std::iter::from_fn(move || {
+        if matches!(data.first(), None | Some(0x00 | 0xFF)) {
+            return None;
+        }
+
+        let (tlv, rest) = read_tlv(data)?;
+
+        data = rest;
+
+        Some(tlv)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_truncated_tlv() {
+        assert!(read_tlv(&[0x04, 0x02, 0x01]).is_none());
+        assert!(read_tlv(&[0x04, 0x84, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]).is_none());
+
+        let (tlv, rest) = read_tlv(&[0x04, 0x81, 0x01, 0xAA, 0x05]).unwrap();
+
+        assert_eq!(0x04, tlv.tag);
+        assert_eq!([0xAA], tlv.value);
+        assert_eq!([0x05], rest);
+    }
+}
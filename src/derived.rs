@@ -0,0 +1,94 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::NHICardBasic;
+
+/// Taiwan has not observed DST since 1980.
+const TAIPEI_OFFSET_SECONDS: i32 = 8 * 60 * 60;
+
+/// Fields computed from the basic data, so that every client formats them the same way.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NHICardDerived {
+    pub age_years:           u32,
+    /// The months after `age_years`.
+    pub age_months:          u32,
+    /// In the `YYYMMDD` format, e.g. `0800102`.
+    pub birth_date_roc:      String,
+    /// E.g. `民國 080 年 01 月 02 日`.
+    pub birth_date_roc_text: String,
+    pub issue_date_roc:      String,
+    pub issue_date_roc_text: String,
+    pub card_age_years:      u32,
+    /// The months after `card_age_years`.
+    pub card_age_months:     u32,
+}
+
+/// Returns today's date in Taipei.
+#[inline]
+pub fn taipei_today() -> NaiveDate {
+    Utc::now().with_timezone(&FixedOffset::east_opt(TAIPEI_OFFSET_SECONDS).unwrap()).date_naive()
+}
+
+/// Formats a date as a ROC date in the `YYYMMDD` format.
+#[inline]
+pub fn roc_date(date: NaiveDate) -> String {
+    format!("{:03}{:02}{:02}", date.year() - 1911, date.month(), date.day())
+}
+
+/// Formats a date like `民國 080 年 01 月 02 日`.
+#[inline]
+pub fn roc_date_text(date: NaiveDate) -> String {
+    format!("民國 {:03} 年 {:02} 月 {:02} 日", date.year() - 1911, date.month(), date.day())
+}
+
+/// Returns the number of complete months from `from` to `to`, or 0 if `to` is earlier.
+fn complete_months(from: NaiveDate, to: NaiveDate) -> u32 {
+    let months = (to.year() - from.year()) * 12 + to.month() as i32
+        - from.month() as i32
+        - i32::from(to.day() < from.day());
+
+    months.max(0) as u32
+}
+
+impl NHICardDerived {
+    pub const FIELD_NAMES: &'static [&'static str] = &[
+        "age_years",
+        "age_months",
+        "birth_date_roc",
+        "birth_date_roc_text",
+        "issue_date_roc",
+        "issue_date_roc_text",
+        "card_age_years",
+        "card_age_months",
+    ];
+}
+
+impl NHICardBasic {
+    /// Computes the derived fields as of `today`, which is usually `taipei_today()`.
+    pub fn derive(&self, today: NaiveDate) -> NHICardDerived {
+        let age = complete_months(self.birth_date, today);
+        let card_age = complete_months(self.issue_date, today);
+
+        NHICardDerived {
+            age_years:           age / 12,
+            age_months:          age % 12,
+            birth_date_roc:      roc_date(self.birth_date),
+            birth_date_roc_text: roc_date_text(self.birth_date),
+            issue_date_roc:      roc_date(self.issue_date),
+            issue_date_roc_text: roc_date_text(self.issue_date),
+            card_age_years:      card_age / 12,
+            card_age_months:     card_age % 12,
+        }
+    }
+
+    /// Returns a copy with the derived fields computed as of today in Taipei if they are absent.
+    pub fn with_derived(&self) -> Self {
+        let mut card = self.clone();
+
+        if card.derived.is_none() {
+            card.derived = Some(card.derive(taipei_today()));
+        }
+
+        card
+    }
+}
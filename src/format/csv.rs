@@ -0,0 +1,74 @@
+use super::{field_names, fields};
+use crate::card::NHICardBasic;
+
+pub const CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+// lets Excel detect UTF-8
+const BOM: &str = "\u{FEFF}";
+
+fn escape(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+/// Renders the cards as CSV with a header row. Lines are terminated by CRLF.
+pub fn to_csv(cards: &[NHICardBasic]) -> String {
+    let mut csv = String::from(BOM);
+
+    csv.push_str(&field_names(cards).join(","));
+    csv.push_str("\r\n");
+
+    for card in cards {
+        let row = fields(card).into_iter().map(|(_, value)| escape(&value)).collect::<Vec<_>>();
+
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::card::Sex;
+
+    #[test]
+    fn escape_fields() {
+        assert_eq!("王小明", escape("王小明"));
+        assert_eq!("\"王,小明\"", escape("王,小明"));
+        assert_eq!("\"王\"\"小明\"\"\"", escape("王\"小明\""));
+        assert_eq!("\"王\r\n小明\"", escape("王\r\n小明"));
+    }
+
+    #[test]
+    fn render() {
+        let mut card = NHICardBasic::builder()
+            .card_no("000012345678")
+            .full_name("王小明")
+            .id_no("A123456789")
+            .birth_date(NaiveDate::from_ymd_opt(1990, 1, 2).unwrap())
+            .sex(Sex::Male)
+            .issue_date(NaiveDate::from_ymd_opt(2020, 3, 4).unwrap())
+            .build()
+            .unwrap();
+
+        card.full_name = String::from("王, \"小明\"");
+
+        let csv = to_csv(&[card]);
+
+        assert!(csv.starts_with(BOM));
+        assert!(csv.ends_with("\r\n"));
+
+        let lines = csv[BOM.len()..].split_terminator("\r\n").collect::<Vec<_>>();
+
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("reader_name,card_no,full_name,id_no,"));
+        assert!(lines[1].starts_with(",000012345678,\"王, \"\"小明\"\"\",A123456789,"));
+    }
+}
@@ -0,0 +1,101 @@
+use serde_json::{json, Value};
+
+use crate::card::{NHICardBasic, Sex};
+
+pub const CONTENT_TYPE: &str = "application/fhir+json";
+
+const PROFILE_PATIENT: &str =
+    "https://twcore.mohw.gov.tw/ig/twcore/StructureDefinition/Patient-twcore";
+const SYSTEM_ID_CARD_NUMBER: &str = "http://www.moi.gov.tw";
+const SYSTEM_IDENTIFIER_TYPE: &str = "http://terminology.hl7.org/CodeSystem/v2-0203";
+
+/// Maps a card to a FHIR R4 `Patient` resource following the TW Core IG.
+pub fn to_patient(card: &NHICardBasic) -> Value {
+    json!({
+        "resourceType": "Patient",
+        "meta": {
+            "profile": [PROFILE_PATIENT],
+        },
+        "identifier": [
+            {
+                "use": "official",
+                "type": {
+                    "coding": [
+                        {
+                            "system": SYSTEM_IDENTIFIER_TYPE,
+                            "code": "NI",
+                        },
+                    ],
+                },
+                "system": SYSTEM_ID_CARD_NUMBER,
+                "value": card.id_no,
+            },
+        ],
+        "active": true,
+        "name": [
+            {
+                "use": "official",
+                "text": card.full_name,
+            },
+        ],
+        "gender": match card.sex {
+            Sex::Male => "male",
+            Sex::Female => "female",
+        },
+        "birthDate": card.birth_date.format("%Y-%m-%d").to_string(),
+    })
+}
+
+/// Wraps the `Patient` resources of the cards in a `searchset` bundle.
+pub fn to_bundle(cards: &[NHICardBasic]) -> Value {
+    json!({
+        "resourceType": "Bundle",
+        "type": "searchset",
+        "total": cards.len(),
+        "entry": cards
+            .iter()
+            .map(|card| json!({
+                "resource": to_patient(card),
+                "search": {
+                    "mode": "match",
+                },
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn patient() {
+        let mut card = NHICardBasic::builder()
+            .card_no("000012345678")
+            .full_name("王小美")
+            .id_no("A223456789")
+            .birth_date(NaiveDate::from_ymd_opt(1990, 1, 2).unwrap())
+            .sex(Sex::Female)
+            .issue_date(NaiveDate::from_ymd_opt(2020, 3, 4).unwrap())
+            .build()
+            .unwrap();
+
+        card.full_name = String::from("王\"小美\"");
+
+        let bundle = to_bundle(&[card]);
+
+        assert_eq!(1, bundle["total"]);
+
+        let patient = &bundle["entry"][0]["resource"];
+
+        assert_eq!("Patient", patient["resourceType"]);
+        assert_eq!("A223456789", patient["identifier"][0]["value"]);
+        assert_eq!("王\"小美\"", patient["name"][0]["text"]);
+        assert_eq!("female", patient["gender"]);
+        assert_eq!("1990-01-02", patient["birthDate"]);
+
+        assert!(serde_json::to_string(&bundle).unwrap().contains(r#""text":"王\"小美\"""#));
+    }
+}
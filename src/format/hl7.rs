@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::prelude::*;
+
+use crate::card::{NHICardBasic, Sex};
+
+pub const CONTENT_TYPE: &str = "x-application/hl7-v2+er7";
+
+pub const SEGMENT_SEPARATOR: char = '\r';
+
+const SENDING_APPLICATION: &str = "TW-NHI-ICC";
+const VERSION: &str = "2.5";
+const CHARACTER_SET: &str = "UNICODE UTF-8";
+
+static MESSAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Escapes the delimiters with the default encoding characters `^~\&`.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("\\F\\"),
+            '^' => escaped.push_str("\\S\\"),
+            '&' => escaped.push_str("\\T\\"),
+            '~' => escaped.push_str("\\R\\"),
+            '\\' => escaped.push_str("\\E\\"),
+            '\r' => escaped.push_str("\\X0D\\"),
+            '\n' => escaped.push_str("\\X0A\\"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Renders a `PID` segment without the trailing segment separator.
+pub fn pid_segment(card: &NHICardBasic, set_id: usize) -> String {
+    format!(
+        "PID|{set_id}||{id_no}^^^MOI^NI~{card_no}^^^NHI^HC||{full_name}||{birth_date}|{sex}",
+        id_no = escape(&card.id_no),
+        card_no = escape(&card.card_no),
+        full_name = escape(&card.full_name),
+        birth_date = card.birth_date.format("%Y%m%d"),
+        sex = match card.sex {
+            Sex::Male => "M",
+            Sex::Female => "F",
+        },
+    )
+}
+
+/// Renders the `PID` segments of the cards, each of which is terminated by the segment separator.
+pub fn pid_segments(cards: &[NHICardBasic]) -> String {
+    cards
+        .iter()
+        .enumerate()
+        .map(|(i, card)| {
+            let mut segment = pid_segment(card, i + 1);
+            segment.push(SEGMENT_SEPARATOR);
+
+            segment
+        })
+        .collect()
+}
+
+/// Renders an `ADT^A04` (register a patient) message skeleton containing `MSH`, `EVN`, `PID` and `PV1` segments.
+pub fn adt_a04(card: &NHICardBasic) -> String {
+    let now = Local::now().format("%Y%m%d%H%M%S").to_string();
+
+    let control_id = format!("{now}{:04}", MESSAGE_COUNTER.fetch_add(1, Ordering::Relaxed) % 10000);
+
+    [
+        format!(
+            "MSH|^~\\&|{SENDING_APPLICATION}||||{now}||ADT^A04^ADT_A01|{control_id}|P|{VERSION}||||||{CHARACTER_SET}"
+        ),
+        format!("EVN|A04|{now}"),
+        pid_segment(card, 1),
+        String::from("PV1|1|O"),
+    ]
+    .into_iter()
+    .map(|mut segment| {
+        segment.push(SEGMENT_SEPARATOR);
+
+        segment
+    })
+    .collect()
+}
+
+/// Renders an `ADT^A04` message for each card.
+pub fn adt_a04_messages(cards: &[NHICardBasic]) -> String {
+    cards.iter().map(adt_a04).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_delimiters_and_line_breaks() {
+        assert_eq!("a\\F\\b\\S\\c\\T\\d\\R\\e\\E\\f", escape("a|b^c&d~e\\f"));
+        assert_eq!("a\\X0D\\\\X0A\\b\\X0A\\c", escape("a\r\nb\nc"));
+        assert_eq!("王小明", escape("王小明"));
+    }
+}
@@ -0,0 +1,165 @@
+pub mod csv;
+pub mod fhir;
+pub mod hl7;
+pub mod text;
+pub mod xml;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::card::{NHICardBasic, NHICardDerived};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Fhir,
+    Hl7,
+    Csv,
+    Xml,
+    Text,
+}
+
+impl OutputFormat {
+    #[inline]
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Fhir => fhir::CONTENT_TYPE,
+            Self::Hl7 => hl7::CONTENT_TYPE,
+            Self::Csv => csv::CONTENT_TYPE,
+            Self::Xml => xml::CONTENT_TYPE,
+            Self::Text => text::CONTENT_TYPE,
+        }
+    }
+
+    pub fn render(self, cards: &[NHICardBasic]) -> String {
+        match self {
+            Self::Json => serde_json::to_string(cards).unwrap(),
+            Self::Fhir => fhir::to_bundle(cards).to_string(),
+            Self::Hl7 => hl7::pid_segments(cards),
+            Self::Csv => csv::to_csv(cards),
+            Self::Xml => xml::to_xml(cards),
+            Self::Text => text::to_text(cards),
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => Some(Self::Json),
+            "application/fhir+json" => Some(Self::Fhir),
+            "x-application/hl7-v2+er7" => Some(Self::Hl7),
+            "text/csv" => Some(Self::Csv),
+            "application/xml" | "text/xml" => Some(Self::Xml),
+            "text/plain" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// Chooses the format from an `Accept` header value. Returns `None` if no acceptable format is supported.
+    ///
+    /// A format other than JSON is only chosen if it is named explicitly with the highest quality in the header, so that a browser, which prefers `text/html` and accepts `*/*`, gets JSON.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut candidates = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parameters = media_range.split(';');
+
+                let media_type = parameters.next()?.trim();
+
+                let q = parameters
+                    .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+
+                Some((media_type.to_ascii_lowercase(), q))
+            })
+            .filter(|(media_type, q)| !media_type.is_empty() && *q > 0.0)
+            .collect::<Vec<_>>();
+
+        // the sort is stable, so the earlier one wins a tie
+        candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let max_q = candidates.first()?.1;
+
+        let explicit = |max_q: f32| {
+            candidates
+                .iter()
+                .filter(|(_, q)| *q >= max_q)
+                .find_map(|(media_type, _)| Self::from_media_type(media_type))
+        };
+
+        let accepts = |media_type: &str| candidates.iter().any(|(m, _)| m == media_type);
+
+        if let Some(format) = explicit(max_q) {
+            Some(format)
+        } else if accepts("*/*") || accepts("application/*") {
+            Some(Self::Json)
+        } else if let Some(format) = explicit(0.0) {
+            Some(format)
+        } else if accepts("text/*") {
+            Some(Self::Text)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the field names of the cards in order. The derived fields are included if the cards have them.
+pub(crate) fn field_names(cards: &[NHICardBasic]) -> Vec<&'static str> {
+    let mut names = NHICardBasic::FIELD_NAMES.to_vec();
+
+    if cards.first().is_some_and(|card| card.derived.is_some()) {
+        names.extend_from_slice(NHICardDerived::FIELD_NAMES);
+    }
+
+    names
+}
+
+/// Returns the serialized fields of a card in the order of `field_names`, with values converted to strings.
+pub(crate) fn fields(card: &NHICardBasic) -> Vec<(&'static str, String)> {
+    let value = serde_json::to_value(card).unwrap();
+
+    field_names(std::slice::from_ref(card))
+        .into_iter()
+        .map(|name| {
+            let value = match value.get(name) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+            };
+
+            (name, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_accept() {
+        assert_eq!(Some(OutputFormat::Json), OutputFormat::from_accept("*/*"));
+        assert_eq!(
+            Some(OutputFormat::Json),
+            OutputFormat::from_accept(
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+            )
+        );
+        assert_eq!(Some(OutputFormat::Csv), OutputFormat::from_accept("text/csv"));
+        assert_eq!(
+            Some(OutputFormat::Xml),
+            OutputFormat::from_accept("application/xml, application/json;q=0.5, */*;q=0.1")
+        );
+        assert_eq!(
+            Some(OutputFormat::Json),
+            OutputFormat::from_accept("text/csv;q=0.5, application/json")
+        );
+        assert_eq!(Some(OutputFormat::Csv), OutputFormat::from_accept("text/html, text/csv;q=0.5"));
+        assert_eq!(Some(OutputFormat::Text), OutputFormat::from_accept("text/*"));
+        assert_eq!(None, OutputFormat::from_accept("text/html, image/png"));
+        assert_eq!(None, OutputFormat::from_accept("application/json;q=0"));
+    }
+}
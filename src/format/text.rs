@@ -0,0 +1,24 @@
+use super::fields;
+use crate::card::NHICardBasic;
+
+pub const CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// Renders each card as `key=value` lines. Cards are separated by an empty line.
+pub fn to_text(cards: &[NHICardBasic]) -> String {
+    let mut text = String::new();
+
+    for (i, card) in cards.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+
+        for (name, value) in fields(card) {
+            text.push_str(name);
+            text.push('=');
+            text.push_str(&value.replace(['\r', '\n'], " "));
+            text.push('\n');
+        }
+    }
+
+    text
+}
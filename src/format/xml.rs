@@ -0,0 +1,76 @@
+use super::fields;
+use crate::card::NHICardBasic;
+
+pub const CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Renders the cards as a `<cards>` document with one `<card>` element per card.
+pub fn to_xml(cards: &[NHICardBasic]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<cards>\n");
+
+    for card in cards {
+        xml.push_str("  <card>\n");
+
+        for (name, value) in fields(card) {
+            xml.push_str(&format!("    <{name}>{}</{name}>\n", escape(&value)));
+        }
+
+        xml.push_str("  </card>\n");
+    }
+
+    xml.push_str("</cards>\n");
+
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::card::Sex;
+
+    #[test]
+    fn escape_markup() {
+        assert_eq!("王小明", escape("王小明"));
+        assert_eq!("&lt;王&amp;小明&gt; &quot;&apos;", escape("<王&小明> \"'"));
+    }
+
+    #[test]
+    fn render() {
+        let mut card = NHICardBasic::builder()
+            .card_no("000012345678")
+            .full_name("王小明")
+            .id_no("A123456789")
+            .birth_date(NaiveDate::from_ymd_opt(1990, 1, 2).unwrap())
+            .sex(Sex::Male)
+            .issue_date(NaiveDate::from_ymd_opt(2020, 3, 4).unwrap())
+            .build()
+            .unwrap();
+
+        card.full_name = String::from("</full_name>王&小明");
+
+        let xml = to_xml(&[card]);
+
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<cards>\n  <card>\n"));
+        assert!(xml.contains("    <full_name>&lt;/full_name&gt;王&amp;小明</full_name>\n"));
+        assert!(xml.contains("    <reader_name></reader_name>\n"));
+        assert!(xml.ends_with("  </card>\n</cards>\n"));
+    }
+}
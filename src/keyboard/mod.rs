@@ -0,0 +1,80 @@
+#[cfg(target_os = "linux")]
+mod uinput;
+#[cfg(not(any(target_os = "linux", windows)))]
+mod unsupported;
+#[cfg(windows)]
+mod windows;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::broadcast::error::RecvError, task};
+#[cfg(target_os = "linux")]
+use uinput::VirtualKeyboard;
+#[cfg(not(any(target_os = "linux", windows)))]
+use unsupported::VirtualKeyboard;
+#[cfg(windows)]
+use windows::VirtualKeyboard;
+
+use crate::{
+    card::{subscribe_card_events, CardEventKind},
+    server::is_reader_reserved,
+    template::CardTemplate,
+};
+
+/// Types a templated string through a virtual keyboard whenever a card is inserted.
+pub struct KeyboardWedge {
+    template:  CardTemplate,
+    key_delay: Duration,
+    keyboard:  Mutex<VirtualKeyboard>,
+}
+
+impl KeyboardWedge {
+    pub fn new(template: CardTemplate, key_delay: Duration) -> anyhow::Result<Self> {
+        let keyboard =
+            VirtualKeyboard::new().map_err(|error| anyhow::anyhow!("無法建立虛擬鍵盤：{error}"))?;
+
+        Ok(Self {
+            template,
+            key_delay,
+            keyboard: Mutex::new(keyboard),
+        })
+    }
+
+    fn type_text(&self, text: &str) {
+        match self.keyboard.lock().unwrap().type_text(text, self.key_delay) {
+            Ok(skipped) if skipped.is_empty() => (),
+            Ok(skipped) => {
+                tracing::warn!(target: "keyboard", count = skipped.len(), "some characters cannot be typed");
+            },
+            Err(error) => tracing::error!(target: "keyboard", ?error),
+        }
+    }
+
+    pub fn spawn_background_tasks(self: &Arc<Self>) {
+        let wedge = self.clone();
+
+        task::spawn(async move {
+            let mut receiver = subscribe_card_events();
+
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if is_reader_reserved(&event.reader_name) => (),
+                    Ok(event) if event.kind == CardEventKind::Inserted => {
+                        let text = wedge.template.render(&event.card);
+                        let wedge = wedge.clone();
+
+                        task::spawn_blocking(move || wedge.type_text(&text)).await.unwrap();
+                    },
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(count)) => {
+                        tracing::warn!(target: "keyboard", count, "card events lagged");
+                    },
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
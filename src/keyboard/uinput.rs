@@ -0,0 +1,238 @@
+use std::{io, thread, time::Duration};
+
+use arboard::Clipboard;
+use evdev::{
+    uinput::{VirtualDevice, VirtualDeviceBuilder},
+    AttributeSet, EventType, InputEvent, Key,
+};
+
+const DEVICE_NAME: &str = "TW NHI IC Card Service Keyboard";
+
+// delay for the desktop environment to pick up the new device
+const DEVICE_READY_DELAY: Duration = Duration::from_millis(500);
+
+// delay for the focused window to fetch the clipboard content after Ctrl+V
+const PASTE_DELAY: Duration = Duration::from_millis(100);
+
+/// Maps a character to a key on the US layout and whether Shift is needed.
+fn char_to_key(c: char) -> Option<(Key, bool)> {
+    let key = match c.to_ascii_lowercase() {
+        'a' => Key::KEY_A,
+        'b' => Key::KEY_B,
+        'c' => Key::KEY_C,
+        'd' => Key::KEY_D,
+        'e' => Key::KEY_E,
+        'f' => Key::KEY_F,
+        'g' => Key::KEY_G,
+        'h' => Key::KEY_H,
+        'i' => Key::KEY_I,
+        'j' => Key::KEY_J,
+        'k' => Key::KEY_K,
+        'l' => Key::KEY_L,
+        'm' => Key::KEY_M,
+        'n' => Key::KEY_N,
+        'o' => Key::KEY_O,
+        'p' => Key::KEY_P,
+        'q' => Key::KEY_Q,
+        'r' => Key::KEY_R,
+        's' => Key::KEY_S,
+        't' => Key::KEY_T,
+        'u' => Key::KEY_U,
+        'v' => Key::KEY_V,
+        'w' => Key::KEY_W,
+        'x' => Key::KEY_X,
+        'y' => Key::KEY_Y,
+        'z' => Key::KEY_Z,
+        _ => {
+            return match c {
+                '1' => Some((Key::KEY_1, false)),
+                '2' => Some((Key::KEY_2, false)),
+                '3' => Some((Key::KEY_3, false)),
+                '4' => Some((Key::KEY_4, false)),
+                '5' => Some((Key::KEY_5, false)),
+                '6' => Some((Key::KEY_6, false)),
+                '7' => Some((Key::KEY_7, false)),
+                '8' => Some((Key::KEY_8, false)),
+                '9' => Some((Key::KEY_9, false)),
+                '0' => Some((Key::KEY_0, false)),
+                '!' => Some((Key::KEY_1, true)),
+                '@' => Some((Key::KEY_2, true)),
+                '#' => Some((Key::KEY_3, true)),
+                '$' => Some((Key::KEY_4, true)),
+                '%' => Some((Key::KEY_5, true)),
+                '^' => Some((Key::KEY_6, true)),
+                '&' => Some((Key::KEY_7, true)),
+                '*' => Some((Key::KEY_8, true)),
+                '(' => Some((Key::KEY_9, true)),
+                ')' => Some((Key::KEY_0, true)),
+                '-' => Some((Key::KEY_MINUS, false)),
+                '_' => Some((Key::KEY_MINUS, true)),
+                '=' => Some((Key::KEY_EQUAL, false)),
+                '+' => Some((Key::KEY_EQUAL, true)),
+                '[' => Some((Key::KEY_LEFTBRACE, false)),
+                '{' => Some((Key::KEY_LEFTBRACE, true)),
+                ']' => Some((Key::KEY_RIGHTBRACE, false)),
+                '}' => Some((Key::KEY_RIGHTBRACE, true)),
+                '\\' => Some((Key::KEY_BACKSLASH, false)),
+                '|' => Some((Key::KEY_BACKSLASH, true)),
+                ';' => Some((Key::KEY_SEMICOLON, false)),
+                ':' => Some((Key::KEY_SEMICOLON, true)),
+                '\'' => Some((Key::KEY_APOSTROPHE, false)),
+                '"' => Some((Key::KEY_APOSTROPHE, true)),
+                '`' => Some((Key::KEY_GRAVE, false)),
+                '~' => Some((Key::KEY_GRAVE, true)),
+                ',' => Some((Key::KEY_COMMA, false)),
+                '<' => Some((Key::KEY_COMMA, true)),
+                '.' => Some((Key::KEY_DOT, false)),
+                '>' => Some((Key::KEY_DOT, true)),
+                '/' => Some((Key::KEY_SLASH, false)),
+                '?' => Some((Key::KEY_SLASH, true)),
+                ' ' => Some((Key::KEY_SPACE, false)),
+                '\t' => Some((Key::KEY_TAB, false)),
+                '\n' | '\r' => Some((Key::KEY_ENTER, false)),
+                _ => None,
+            };
+        },
+    };
+
+    Some((key, c.is_ascii_uppercase()))
+}
+
+/// Types with the US layout. The characters which have no keys, e.g. Chinese names, are pasted from the clipboard with Ctrl+V, and the previous clipboard content is restored afterwards.
+pub(super) struct VirtualKeyboard {
+    device:          VirtualDevice,
+    /// `None` if the clipboard is not available, e.g. there is no display, in which case the characters which have no keys are skipped.
+    clipboard:       Option<Clipboard>,
+    /// The clipboard text before the first paste of the current text, which is `None` if it is not text.
+    saved_clipboard: Option<Option<String>>,
+}
+
+impl VirtualKeyboard {
+    pub(super) fn new() -> io::Result<Self> {
+        let mut keys = AttributeSet::<Key>::new();
+
+        keys.insert(Key::KEY_LEFTSHIFT);
+        keys.insert(Key::KEY_LEFTCTRL);
+
+        for c in (0x20u8..0x7F).map(char::from).chain(['\t', '\n']) {
+            if let Some((key, _)) = char_to_key(c) {
+                keys.insert(key);
+            }
+        }
+
+        let device = VirtualDeviceBuilder::new()?.name(DEVICE_NAME).with_keys(&keys)?.build()?;
+
+        let clipboard = match Clipboard::new() {
+            Ok(clipboard) => Some(clipboard),
+            Err(error) => {
+                tracing::warn!(target: "keyboard", ?error, "non-ASCII characters cannot be pasted");
+
+                None
+            },
+        };
+
+        thread::sleep(DEVICE_READY_DELAY);
+
+        Ok(Self {
+            device,
+            clipboard,
+            saved_clipboard: None,
+        })
+    }
+
+    #[inline]
+    fn emit_key(&mut self, key: Key, value: i32) -> io::Result<()> {
+        self.device.emit(&[InputEvent::new(EventType::KEY, key.code(), value)])
+    }
+
+    fn press_key(&mut self, key: Key, modifier: Option<Key>) -> io::Result<()> {
+        if let Some(modifier) = modifier {
+            self.emit_key(modifier, 1)?;
+        }
+
+        self.emit_key(key, 1)?;
+        self.emit_key(key, 0)?;
+
+        if let Some(modifier) = modifier {
+            self.emit_key(modifier, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pastes the text with Ctrl+V. Returns whether it is pasted.
+    fn paste(&mut self, text: &str) -> io::Result<bool> {
+        let Some(clipboard) = self.clipboard.as_mut() else {
+            return Ok(false);
+        };
+
+        if self.saved_clipboard.is_none() {
+            self.saved_clipboard = Some(clipboard.get_text().ok());
+        }
+
+        if let Err(error) = clipboard.set_text(text) {
+            tracing::warn!(target: "keyboard", ?error, "cannot set the clipboard");
+
+            return Ok(false);
+        }
+
+        self.press_key(Key::KEY_V, Some(Key::KEY_LEFTCTRL))?;
+
+        thread::sleep(PASTE_DELAY);
+
+        Ok(true)
+    }
+
+    /// Types the text and returns the characters which can neither be typed nor pasted.
+    pub(super) fn type_text(&mut self, text: &str, key_delay: Duration) -> io::Result<Vec<char>> {
+        let mut skipped = Vec::new();
+        let mut untypeable = String::new();
+
+        let result = (|| {
+            // a trailing `None` flushes the characters to paste
+            for c in text.chars().map(Some).chain([None]) {
+                let key = c.and_then(char_to_key);
+
+                if key.is_none() {
+                    if let Some(c) = c {
+                        untypeable.push(c);
+
+                        continue;
+                    }
+                }
+
+                if !untypeable.is_empty() {
+                    if !self.paste(&untypeable)? {
+                        skipped.extend(untypeable.chars());
+                    }
+
+                    untypeable.clear();
+                }
+
+                if let Some((key, shift)) = key {
+                    self.press_key(key, shift.then_some(Key::KEY_LEFTSHIFT))?;
+
+                    thread::sleep(key_delay);
+                }
+            }
+
+            Ok(())
+        })();
+
+        // the card data is not left in the clipboard even if the previous content is not text
+        if let (Some(clipboard), Some(previous)) =
+            (self.clipboard.as_mut(), self.saved_clipboard.take())
+        {
+            let restored = match previous {
+                Some(previous) => clipboard.set_text(previous),
+                None => clipboard.clear(),
+            };
+
+            if let Err(error) = restored {
+                tracing::warn!(target: "keyboard", ?error, "cannot restore the clipboard");
+            }
+        }
+
+        result.map(|_| skipped)
+    }
+}
@@ -0,0 +1,21 @@
+use std::{io, time::Duration};
+
+pub(super) struct VirtualKeyboard;
+
+impl VirtualKeyboard {
+    #[inline]
+    pub(super) fn new() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only Linux (uinput) and Windows (SendInput) are supported",
+        ))
+    }
+
+    #[inline]
+    pub(super) fn type_text(&mut self, _text: &str, _key_delay: Duration) -> io::Result<Vec<char>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only Linux (uinput) and Windows (SendInput) are supported",
+        ))
+    }
+}
@@ -0,0 +1,80 @@
+use std::{io, mem, thread, time::Duration};
+
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
+    KEYEVENTF_UNICODE, VIRTUAL_KEY, VK_RETURN, VK_TAB,
+};
+
+/// Types with `SendInput`. Characters other than Tab and Enter are sent as Unicode (UTF-16) key events, so they do not depend on the keyboard layout or the IME.
+pub(super) struct VirtualKeyboard;
+
+#[inline]
+fn key_input(virtual_key: VIRTUAL_KEY, scan_code: u16, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type:    INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk:         virtual_key,
+                wScan:       scan_code,
+                dwFlags:     flags,
+                time:        0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+impl VirtualKeyboard {
+    #[inline]
+    pub(super) fn new() -> io::Result<Self> {
+        Ok(Self)
+    }
+
+    fn send(inputs: &[INPUT]) -> io::Result<()> {
+        let sent = unsafe {
+            SendInput(inputs.len() as u32, inputs.as_ptr(), mem::size_of::<INPUT>() as i32)
+        };
+
+        // blocked by UIPI, e.g. the focused window runs as administrator
+        if sent as usize != inputs.len() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Types the text and returns the characters which cannot be typed, which is always empty.
+    pub(super) fn type_text(&mut self, text: &str, key_delay: Duration) -> io::Result<Vec<char>> {
+        let mut inputs = Vec::with_capacity(4);
+
+        for c in text.chars() {
+            inputs.clear();
+
+            match c {
+                '\t' | '\n' | '\r' => {
+                    let virtual_key = if c == '\t' { VK_TAB } else { VK_RETURN };
+
+                    inputs.push(key_input(virtual_key, 0, 0));
+                    inputs.push(key_input(virtual_key, 0, KEYEVENTF_KEYUP));
+                },
+                _ => {
+                    let mut units = [0; 2];
+
+                    for &unit in c.encode_utf16(&mut units).iter() {
+                        inputs.push(key_input(0, unit, KEYEVENTF_UNICODE));
+                    }
+
+                    for &unit in c.encode_utf16(&mut units).iter() {
+                        inputs.push(key_input(0, unit, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP));
+                    }
+                },
+            }
+
+            Self::send(&inputs)?;
+
+            thread::sleep(key_delay);
+        }
+
+        Ok(Vec::new())
+    }
+}
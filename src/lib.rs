@@ -3,3 +3,22 @@
 
 Read Taiwan NHI cards via HTTP API. 透過 HTTP API 讀取中華民國健保卡。
 */
+
+mod api;
+mod big5;
+mod card_record;
+#[cfg(feature = "client")]
+mod client;
+mod der;
+mod derived;
+mod moica_certificate;
+mod nhi_card_basic;
+
+pub use api::*;
+pub use big5::*;
+pub use card_record::*;
+#[cfg(feature = "client")]
+pub use client::*;
+pub use derived::*;
+pub use moica_certificate::*;
+pub use nhi_card_basic::*;
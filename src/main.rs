@@ -1,23 +1,170 @@
+mod audit;
 mod card;
 mod cli;
+mod clipboard;
+mod format;
+mod keyboard;
+mod metrics;
+mod mllp;
+mod mqtt;
 mod server;
+mod template;
+mod webhook;
 
-use std::net::SocketAddr;
+use std::{fs, net::SocketAddr, sync::Arc, time::Duration};
 
+use audit::AuditLog;
+use card::{
+    set_backend, set_cooperative, set_derived_fields, set_name_decoding, set_reader_timeout,
+    spawn_card_poller, Big5Extensions, CardBackend, NameDecoding, PcscBackend, RecordingBackend,
+    ReplayBackend, Simulator,
+};
 use cli::*;
+use clipboard::ClipboardCopier;
+use keyboard::KeyboardWedge;
+use mllp::MllpSender;
+use mqtt::{MqttConfig, MqttPublisher};
 use server::*;
 use tokio::runtime;
+use webhook::{Webhook, WebhookConfig};
 
 fn main() -> anyhow::Result<()> {
     let args = get_args();
 
     let socket_addr = SocketAddr::new(args.interface, args.port);
 
+    set_name_decoding(NameDecoding {
+        lenient:    args.lenient_names,
+        extensions: match args.big5_extension_map {
+            Some(path) => Big5Extensions::parse(&fs::read_to_string(path)?)?,
+            None => Big5Extensions::default(),
+        },
+    });
+
+    set_derived_fields(args.derived_fields);
+    set_reader_timeout(Duration::from_secs(args.reader_timeout));
+
+    if args.cooperative {
+        set_cooperative(Duration::from_secs(args.quiet_period));
+    }
+
+    let simulator = match args.command {
+        Some(CLICommand::Simulate {
+            cards,
+        }) => {
+            if args.replay.is_some() {
+                anyhow::bail!("模擬模式不能與 --replay 同時使用");
+            }
+
+            Some(Simulator::load(cards)?)
+        },
+        None => None,
+    };
+
+    if let Some(path) = args.replay {
+        set_backend(Box::new(ReplayBackend::open(path)?));
+    } else {
+        let backend: Box<dyn CardBackend> = match simulator.as_ref() {
+            Some(simulator) => Box::new(simulator.backend()),
+            None => Box::<PcscBackend>::default(),
+        };
+
+        match args.record {
+            Some(path) => {
+                set_backend(Box::new(RecordingBackend::new(backend, path, args.record_scramble)?))
+            },
+            None => set_backend(backend),
+        }
+    }
+
+    let audit_log = match args.audit_log {
+        Some(path) => Some(Arc::new(AuditLog::open(
+            path,
+            args.audit_log_format,
+            args.audit_hash_key.unwrap(),
+            args.audit_retention_days,
+        )?)),
+        None => None,
+    };
+
+    let webhook = if args.webhook_urls.is_empty() {
+        None
+    } else {
+        Some(Arc::new(Webhook::new(WebhookConfig {
+            urls:        args.webhook_urls,
+            secret:      args.webhook_secret,
+            mask:        args.webhook_mask,
+            max_retries: args.webhook_max_retries,
+            dead_letter: args.webhook_dead_letter,
+        })?))
+    };
+
+    let keyboard_wedge = match args.keyboard_wedge_template {
+        Some(template) => Some(Arc::new(KeyboardWedge::new(
+            template,
+            Duration::from_millis(args.keyboard_wedge_key_delay),
+        )?)),
+        None => None,
+    };
+
+    let clipboard_copier = match args.clipboard_template {
+        Some(template) => Some(ClipboardCopier::new(template, match args.clipboard_clear_after {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        })?),
+        None => None,
+    };
+
     let runtime = runtime::Runtime::new()?;
 
     runtime.block_on(async move {
+        if let Some(audit_log) = audit_log.as_ref() {
+            audit_log.spawn_background_tasks();
+        }
+
+        if let Some(webhook) = webhook.as_ref() {
+            webhook.spawn_background_tasks();
+        }
+
+        if let Some(keyboard_wedge) = keyboard_wedge.as_ref() {
+            keyboard_wedge.spawn_background_tasks();
+        }
+
+        if let Some(clipboard_copier) = clipboard_copier {
+            clipboard_copier.spawn_background_tasks();
+        }
+
+        if let Some(address) = args.hl7_mllp {
+            Arc::new(MllpSender::new(address)).spawn_background_tasks();
+        }
+
+        spawn_card_poller(Duration::from_secs(args.poll_interval));
+
+        if let Some(host) = args.mqtt_host {
+            MqttPublisher::start(MqttConfig {
+                host,
+                port: args.mqtt_port,
+                tls: args.mqtt_tls,
+                ca_file: args.mqtt_ca_file,
+                username: args.mqtt_username,
+                password: args.mqtt_password,
+                client_id: args.mqtt_client_id,
+                topic_prefix: args.mqtt_topic_prefix,
+                qos: args.mqtt_qos,
+                mask: args.mqtt_mask,
+            })?;
+        }
+
         server_main(socket_addr, AppState {
             default_card_fetch_interval: args.default_ws_card_fetch_interval,
+            ws_ping_interval: Duration::from_secs(args.ws_ping_interval),
+            ws_pong_timeout: Duration::from_secs(args.ws_pong_timeout),
+            ws_max_connections: args.ws_max_connections,
+            admin_token: args.admin_token,
+            audit_log,
+            simulator,
+            lease_ttl: Duration::from_secs(args.lease_ttl),
+            apdu_allowlist: Arc::from(args.apdu_allowlist),
         })
         .await
     })
@@ -0,0 +1,93 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+pub static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some(String::from("tw_nhi_icc")), None).unwrap();
+
+    registry.register(Box::new(CARD_READS.clone())).unwrap();
+    registry.register(Box::new(CONTEXT_REESTABLISHMENTS.clone())).unwrap();
+    registry.register(Box::new(APDU_DURATION.clone())).unwrap();
+    registry.register(Box::new(WS_SESSIONS_ACTIVE.clone())).unwrap();
+    registry.register(Box::new(HTTP_REQUESTS.clone())).unwrap();
+    registry.register(Box::new(CARD_READ_TIMEOUTS.clone())).unwrap();
+
+    registry
+});
+
+pub static CARD_READS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(Opts::new("card_reads_total", "Card reads by result."), &["result"]).unwrap()
+});
+
+pub static CONTEXT_REESTABLISHMENTS: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "pcsc_context_reestablishments_total",
+        "Number of times the PC/SC context has been re-established.",
+    )
+    .unwrap()
+});
+
+pub static APDU_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new("apdu_duration_seconds", "APDU transmission latency.")
+            .buckets(vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]),
+        &["command"],
+    )
+    .unwrap()
+});
+
+pub static WS_SESSIONS_ACTIVE: Lazy<IntGauge> = Lazy::new(|| {
+    IntGauge::new("websocket_sessions_active", "Number of active WebSocket sessions.").unwrap()
+});
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(Opts::new("http_requests_total", "HTTP requests by route and status."), &[
+        "route", "status",
+    ])
+    .unwrap()
+});
+
+pub static CARD_READ_TIMEOUTS: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new("card_read_timeouts_total", "Number of card reads that timed out.").unwrap()
+});
+
+#[derive(Debug, Clone, Copy)]
+pub enum CardReadResult {
+    Ok,
+    Cached,
+    NoCard,
+    Unsupported,
+    ParseError,
+    Error,
+    Busy,
+}
+
+impl CardReadResult {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Cached => "cached",
+            Self::NoCard => "no_card",
+            Self::Unsupported => "unsupported",
+            Self::ParseError => "parse_error",
+            Self::Error => "error",
+            Self::Busy => "busy",
+        }
+    }
+}
+
+#[inline]
+pub fn record_card_read(result: CardReadResult) {
+    CARD_READS.with_label_values(&[result.as_str()]).inc();
+}
+
+pub fn encode() -> String {
+    let mut buffer = Vec::new();
+
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer).unwrap();
+
+    String::from_utf8(buffer).unwrap()
+}
@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::broadcast::error::RecvError,
+    task, time,
+};
+
+use crate::{
+    card::{subscribe_card_events, CardEventKind},
+    format::hl7,
+    server::is_reader_reserved,
+};
+
+const START_BLOCK: u8 = 0x0B;
+const END_BLOCK: &[u8] = b"\x1C\x0D";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ACK_LENGTH: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub struct MllpSender {
+    address: String,
+}
+
+impl MllpSender {
+    #[inline]
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+        }
+    }
+
+    /// Sends a message and returns the acknowledgment message.
+    async fn send(&self, message: &str) -> anyhow::Result<String> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+
+        let mut frame = Vec::with_capacity(message.len() + 3);
+        frame.push(START_BLOCK);
+        frame.extend_from_slice(message.as_bytes());
+        frame.extend_from_slice(END_BLOCK);
+
+        stream.write_all(&frame).await?;
+        stream.flush().await?;
+
+        let mut ack = Vec::new();
+        let mut buffer = [0u8; 1024];
+
+        while !ack.ends_with(END_BLOCK) {
+            let c = stream.read(&mut buffer).await?;
+
+            if c == 0 {
+                anyhow::bail!("the connection is closed before receiving an acknowledgment");
+            }
+
+            ack.extend_from_slice(&buffer[..c]);
+
+            if ack.len() > MAX_ACK_LENGTH {
+                anyhow::bail!("the acknowledgment is too long");
+            }
+        }
+
+        let ack = &ack[..ack.len() - END_BLOCK.len()];
+        let ack = ack.strip_prefix(&[START_BLOCK]).unwrap_or(ack);
+
+        Ok(String::from_utf8_lossy(ack).into_owned())
+    }
+
+    /// Sends an `ADT^A04` message whenever a card is inserted.
+    pub fn spawn_background_tasks(self: &Arc<Self>) {
+        let sender = self.clone();
+
+        task::spawn(async move {
+            let mut receiver = subscribe_card_events();
+
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if is_reader_reserved(&event.reader_name) => (),
+                    Ok(event) if event.kind == CardEventKind::Inserted => {
+                        let message = hl7::adt_a04(&event.card);
+
+                        match time::timeout(TIMEOUT, sender.send(&message)).await {
+                            Ok(Ok(ack)) => {
+                                let accepted = ack.split(hl7::SEGMENT_SEPARATOR).any(|segment| {
+                                    segment.starts_with("MSA|AA") || segment.starts_with("MSA|CA")
+                                });
+
+                                if accepted {
+                                    tracing::debug!(target: "mllp", address = sender.address, "accepted");
+                                } else {
+                                    tracing::warn!(target: "mllp", address = sender.address, ack, "not accepted");
+                                }
+                            },
+                            Ok(Err(error)) => {
+                                tracing::warn!(target: "mllp", address = sender.address, ?error);
+                            },
+                            Err(_) => {
+                                tracing::warn!(target: "mllp", address = sender.address, "timeout");
+                            },
+                        }
+                    },
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(count)) => {
+                        tracing::warn!(target: "mllp", count, "card events lagged");
+                    },
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
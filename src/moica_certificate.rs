@@ -0,0 +1,330 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::der::{self, Tlv};
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_UTF8_STRING: u8 = 0x0C;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_BMP_STRING: u8 = 0x1E;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_VERSION: u8 = 0xA0;
+/// `certificates [4]` in the object directory file.
+const TAG_ODF_CERTIFICATES: u8 = 0xA4;
+/// `typeAttributes [1]` of a certificate object.
+const TAG_TYPE_ATTRIBUTES: u8 = 0xA1;
+
+/// 2.5.4.3
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// 2.5.4.5
+const OID_SERIAL_NUMBER: &[u8] = &[0x55, 0x04, 0x05];
+
+#[derive(Debug)]
+pub struct MOICACardParseError;
+
+impl Display for MOICACardParseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("不是正確的自然人憑證")
+    }
+}
+
+impl Error for MOICACardParseError {}
+
+/// An X.509 certificate stored in a MOICA card.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MOICACertificate {
+    /// The label in the certificate directory of the card.
+    pub label:                 Option<String>,
+    /// The DER encoding in base64.
+    pub certificate:           String,
+    /// In uppercase hex.
+    pub serial_number:         String,
+    pub subject_cn:            Option<String>,
+    /// The `serialNumber` attribute of the subject.
+    pub subject_serial_number: Option<String>,
+    pub not_before:            DateTime<Local>,
+    pub not_after:             DateTime<Local>,
+}
+
+/// The public data of a MOICA (自然人憑證) card, i.e. the certificates, which can be read without the PIN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MOICACard {
+    pub reader_name:  Option<String>,
+    pub certificates: Vec<MOICACertificate>,
+    /// Generated when the card is inserted, and stable until it is removed.
+    pub session_id:   Option<String>,
+    pub inserted_at:  Option<DateTime<Local>>,
+}
+
+/// Descends into the first elements of sequences until a `Path`, whose first element is an octet string, is found.
+fn find_path(data: &[u8]) -> Option<Vec<u8>> {
+    let (tlv, _) = der::read_tlv(data)?;
+
+    match tlv.tag {
+        TAG_OCTET_STRING => Some(tlv.value.to_vec()),
+        TAG_SEQUENCE => find_path(tlv.value),
+        _ => None,
+    }
+}
+
+fn decode_string(tlv: Tlv<'_>) -> Option<String> {
+    match tlv.tag {
+        TAG_BMP_STRING => {
+            let units = tlv
+                .value
+                .chunks_exact(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>();
+
+            Some(String::from_utf16_lossy(&units))
+        },
+        // PrintableString, T61String, IA5String and UTF8String
+        0x13 | 0x14 | 0x16 | TAG_UTF8_STRING => {
+            Some(String::from_utf8_lossy(tlv.value).into_owned())
+        },
+        _ => None,
+    }
+}
+
+fn decode_time(tlv: Tlv<'_>) -> Option<DateTime<Local>> {
+    let s = std::str::from_utf8(tlv.value).ok()?.strip_suffix('Z')?;
+
+    let s = match tlv.tag {
+        TAG_UTC_TIME => {
+            let century = if s.get(..2)?.parse::<u8>().ok()? < 50 { "20" } else { "19" };
+
+            format!("{century}{s}")
+        },
+        TAG_GENERALIZED_TIME => String::from(s),
+        _ => return None,
+    };
+
+    let date_time = NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M%S").ok()?;
+
+    Some(date_time.and_utc().with_timezone(&Local))
+}
+
+/// Returns the value of an attribute of a distinguished name.
+fn find_attribute(name: &[u8], oid: &[u8]) -> Option<String> {
+    der::children(name).flat_map(|set| der::children(set.value)).find_map(|attribute| {
+        let mut fields = der::children(attribute.value);
+
+        let id = fields.next().filter(|tlv| tlv.tag == TAG_OID)?;
+
+        if id.value == oid {
+            decode_string(fields.next()?)
+        } else {
+            None
+        }
+    })
+}
+
+impl MOICACertificate {
+    /// Returns the length of a DER-encoded certificate from its first bytes, so that it can be read from a file which may be padded.
+    #[inline]
+    pub fn encoded_length(header: &[u8]) -> Option<usize> {
+        der::read_header(header)
+            .and_then(|(header_length, length)| header_length.checked_add(length))
+    }
+
+    pub fn from_der<D: AsRef<[u8]>>(
+        label: Option<String>,
+        data: D,
+    ) -> Result<Self, MOICACardParseError> {
+        let data = data.as_ref();
+
+        let length = Self::encoded_length(data).ok_or(MOICACardParseError)?;
+        let data = data.get(..length).ok_or(MOICACardParseError)?;
+
+        let (certificate, _) = der::read_tlv(data)
+            .filter(|(tlv, _)| tlv.tag == TAG_SEQUENCE)
+            .ok_or(MOICACardParseError)?;
+
+        let tbs = der::children(certificate.value)
+            .next()
+            .filter(|tlv| tlv.tag == TAG_SEQUENCE)
+            .ok_or(MOICACardParseError)?;
+
+        let mut fields = der::children(tbs.value).peekable();
+
+        fields.next_if(|tlv| tlv.tag == TAG_VERSION);
+
+        let serial_number =
+            fields.next().filter(|tlv| tlv.tag == TAG_INTEGER).ok_or(MOICACardParseError)?;
+
+        // the signature algorithm and the issuer
+        fields.nth(1).ok_or(MOICACardParseError)?;
+
+        let validity =
+            fields.next().filter(|tlv| tlv.tag == TAG_SEQUENCE).ok_or(MOICACardParseError)?;
+
+        let mut times = der::children(validity.value).map(decode_time);
+
+        let (Some(Some(not_before)), Some(Some(not_after))) = (times.next(), times.next()) else {
+            return Err(MOICACardParseError);
+        };
+
+        let subject =
+            fields.next().filter(|tlv| tlv.tag == TAG_SEQUENCE).ok_or(MOICACardParseError)?;
+
+        Ok(Self {
+            label,
+            certificate: BASE64.encode(data),
+            serial_number: hex::encode_upper(serial_number.value),
+            subject_cn: find_attribute(subject.value, OID_COMMON_NAME),
+            subject_serial_number: find_attribute(subject.value, OID_SERIAL_NUMBER),
+            not_before,
+            not_after,
+        })
+    }
+}
+
+impl MOICACard {
+    /// Returns the paths of the certificate directory files in the object directory file of PKCS #15.
+    pub fn parse_odf<D: AsRef<[u8]>>(odf: D) -> Vec<Vec<u8>> {
+        der::children(odf.as_ref())
+            .filter(|tlv| tlv.tag == TAG_ODF_CERTIFICATES)
+            .filter_map(|tlv| find_path(tlv.value))
+            .collect()
+    }
+
+    /// Returns the labels and the paths of the X.509 certificates in a certificate directory file of PKCS #15.
+    pub fn parse_cdf<D: AsRef<[u8]>>(cdf: D) -> Vec<(Option<String>, Vec<u8>)> {
+        der::children(cdf.as_ref())
+            .filter(|tlv| tlv.tag == TAG_SEQUENCE)
+            .filter_map(|object| {
+                let mut fields = der::children(object.value);
+
+                let label = der::children(fields.next()?.value)
+                    .find(|tlv| tlv.tag == TAG_UTF8_STRING)
+                    .and_then(decode_string);
+
+                let type_attributes = fields.find(|tlv| tlv.tag == TAG_TYPE_ATTRIBUTES)?;
+
+                Some((label, find_path(type_attributes.value)?))
+            })
+            .collect()
+    }
+
+    /// The serial number of the first certificate, which identifies the card.
+    #[inline]
+    pub fn card_id(&self) -> &str {
+        self.certificates
+            .first()
+            .map(|certificate| certificate.serial_number.as_str())
+            .unwrap_or("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let value = parts.concat();
+
+        let mut data = vec![tag];
+
+        if value.len() < 0x80 {
+            data.push(value.len() as u8);
+        } else {
+            data.push(0x82);
+            data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+
+        data.extend_from_slice(&value);
+
+        data
+    }
+
+    fn attribute(oid: &[u8], tag: u8, value: &[u8]) -> Vec<u8> {
+        tlv(0x31, &[&tlv(TAG_SEQUENCE, &[&tlv(TAG_OID, &[oid]), &tlv(tag, &[value])])])
+    }
+
+    fn certificate() -> Vec<u8> {
+        let name = "王小明".encode_utf16().flat_map(u16::to_be_bytes).collect::<Vec<_>>();
+
+        let subject = tlv(TAG_SEQUENCE, &[
+            &attribute(&[0x55, 0x04, 0x06], 0x13, b"TW"),
+            &attribute(OID_COMMON_NAME, TAG_BMP_STRING, &name),
+            &attribute(OID_SERIAL_NUMBER, 0x13, b"0000000112831644"),
+        ]);
+
+        let tbs = tlv(TAG_SEQUENCE, &[
+            &tlv(TAG_VERSION, &[&tlv(TAG_INTEGER, &[&[2]])]),
+            &tlv(TAG_INTEGER, &[&[0x01, 0x23, 0xAB]]),
+            &tlv(TAG_SEQUENCE, &[&tlv(TAG_OID, &[&[0x2A, 0x86, 0x48]])]),
+            &tlv(TAG_SEQUENCE, &[]),
+            &tlv(TAG_SEQUENCE, &[
+                &tlv(TAG_UTC_TIME, &[b"210505000000Z"]),
+                &tlv(TAG_GENERALIZED_TIME, &[b"20310505000000Z"]),
+            ]),
+            &subject,
+        ]);
+
+        tlv(TAG_SEQUENCE, &[&tbs, &tlv(TAG_SEQUENCE, &[]), &tlv(0x03, &[&[0]])])
+    }
+
+    #[test]
+    fn certificate_fields() {
+        let mut data = certificate();
+
+        assert_eq!(Some(data.len()), MOICACertificate::encoded_length(&data[..4]));
+
+        // padding of the file
+        data.extend_from_slice(&[0xFF; 8]);
+
+        let certificate = MOICACertificate::from_der(Some(String::from("sign")), &data).unwrap();
+
+        assert_eq!("0123AB", certificate.serial_number);
+        assert_eq!(Some("王小明"), certificate.subject_cn.as_deref());
+        assert_eq!(Some("0000000112831644"), certificate.subject_serial_number.as_deref());
+        assert_eq!(
+            Utc.with_ymd_and_hms(2021, 5, 5, 0, 0, 0).unwrap(),
+            certificate.not_before.with_timezone(&Utc)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2031, 5, 5, 0, 0, 0).unwrap(),
+            certificate.not_after.with_timezone(&Utc)
+        );
+        assert_eq!(BASE64.encode(&data[..data.len() - 8]), certificate.certificate);
+
+        assert!(MOICACertificate::from_der(None, &data[..20]).is_err());
+    }
+
+    #[test]
+    fn directories() {
+        let odf = [
+            tlv(0xA0, &[&tlv(TAG_SEQUENCE, &[&tlv(TAG_OCTET_STRING, &[&[
+                0x3F, 0x00, 0x44, 0x00,
+            ]])])]),
+            tlv(TAG_ODF_CERTIFICATES, &[&tlv(TAG_SEQUENCE, &[&tlv(TAG_OCTET_STRING, &[&[
+                0x3F, 0x00, 0x50, 0x15, 0x44, 0x04,
+            ]])])]),
+            vec![0x00; 4],
+        ]
+        .concat();
+
+        assert_eq!(vec![vec![0x3F, 0x00, 0x50, 0x15, 0x44, 0x04]], MOICACard::parse_odf(odf));
+
+        let path = tlv(TAG_SEQUENCE, &[&tlv(TAG_OCTET_STRING, &[&[0x43, 0x01]])]);
+
+        let cdf = tlv(TAG_SEQUENCE, &[
+            &tlv(TAG_SEQUENCE, &[&tlv(TAG_UTF8_STRING, &[b"sign"]), &tlv(0x03, &[&[0]])]),
+            &tlv(TAG_SEQUENCE, &[&tlv(TAG_OCTET_STRING, &[&[0x45]])]),
+            &tlv(TAG_TYPE_ATTRIBUTES, &[&tlv(TAG_SEQUENCE, &[&path])]),
+        ]);
+
+        assert_eq!(vec![(Some(String::from("sign")), vec![0x43, 0x01])], MOICACard::parse_cdf(cdf));
+    }
+}
@@ -0,0 +1,201 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS, Transport};
+use tokio::{sync::broadcast::error::RecvError, task, time};
+
+use crate::{
+    card::{subscribe_card_events, subscribe_reader_names, CardEvent, CardEventKind},
+    server::is_reader_reserved,
+};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+const STATUS_ONLINE: &str = "online";
+const STATUS_OFFLINE: &str = "offline";
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host:         String,
+    pub port:         u16,
+    pub tls:          bool,
+    pub ca_file:      Option<PathBuf>,
+    pub username:     Option<String>,
+    pub password:     Option<String>,
+    pub client_id:    String,
+    pub topic_prefix: String,
+    pub qos:          u8,
+    pub mask:         bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttPublisher {
+    config: MqttConfig,
+    client: AsyncClient,
+    qos:    QoS,
+}
+
+/// Replaces the characters which cannot be used in a topic level.
+#[inline]
+fn topic_level(s: &str) -> String {
+    s.chars().map(|c| if matches!(c, '/' | '+' | '#') { '_' } else { c }).collect()
+}
+
+impl MqttPublisher {
+    #[inline]
+    fn status_topic(&self) -> String {
+        format!("{}/status", self.config.topic_prefix)
+    }
+
+    #[inline]
+    fn events_topic(&self) -> String {
+        format!("{}/events", self.config.topic_prefix)
+    }
+
+    #[inline]
+    fn readers_topic(&self) -> String {
+        format!("{}/readers", self.config.topic_prefix)
+    }
+
+    #[inline]
+    fn reader_card_topic(&self, reader_name: &str) -> String {
+        format!("{}/readers/{}/card", self.config.topic_prefix, topic_level(reader_name))
+    }
+
+    /// Connects to the broker in the background and publishes card events until the runtime is shut down.
+    pub fn start(config: MqttConfig) -> anyhow::Result<Self> {
+        let qos = rumqttc::qos(config.qos)?;
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            format!("{}/status", config.topic_prefix),
+            STATUS_OFFLINE,
+            qos,
+            true,
+        ));
+
+        if let Some(username) = config.username.as_ref() {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        if config.tls {
+            options.set_transport(match config.ca_file.as_ref() {
+                Some(ca_file) => Transport::tls(fs::read(ca_file)?, None, None),
+                None => Transport::tls_with_default_config(),
+            });
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 64);
+
+        let publisher = Self {
+            config,
+            client,
+            qos,
+        };
+
+        {
+            let publisher = publisher.clone();
+
+            task::spawn(async move {
+                loop {
+                    match event_loop.poll().await {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            tracing::info!(target: "mqtt", "connected");
+
+                            // the event loop cannot wait for its own request channel
+                            if let Err(error) = publisher.client.try_publish(
+                                publisher.status_topic(),
+                                publisher.qos,
+                                true,
+                                STATUS_ONLINE,
+                            ) {
+                                tracing::warn!(target: "mqtt", ?error);
+                            }
+                        },
+                        Ok(_) => (),
+                        Err(error) => {
+                            tracing::warn!(target: "mqtt", ?error);
+
+                            time::sleep(RECONNECT_DELAY).await;
+                        },
+                    }
+                }
+            });
+        }
+
+        {
+            let publisher = publisher.clone();
+
+            task::spawn(async move {
+                let mut receiver = subscribe_card_events();
+
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if is_reader_reserved(&event.reader_name) => (),
+                        Ok(event) => publisher.publish_card_event(&event).await,
+                        Err(RecvError::Lagged(count)) => {
+                            tracing::warn!(target: "mqtt", count, "card events lagged");
+                        },
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+
+        {
+            let publisher = publisher.clone();
+
+            task::spawn(async move {
+                let mut receiver = subscribe_reader_names();
+
+                loop {
+                    let reader_names = receiver.borrow_and_update().clone();
+
+                    if let Some(reader_names) = reader_names {
+                        publisher
+                            .publish(
+                                publisher.readers_topic(),
+                                true,
+                                serde_json::to_vec(&reader_names).unwrap(),
+                            )
+                            .await;
+                    }
+
+                    if receiver.changed().await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Ok(publisher)
+    }
+
+    async fn publish<P: Into<Vec<u8>>>(&self, topic: String, retain: bool, payload: P) {
+        if let Err(error) = self.client.publish(&topic, self.qos, retain, payload).await {
+            tracing::warn!(target: "mqtt", topic, ?error);
+        }
+    }
+
+    async fn publish_card_event(&self, event: &CardEvent) {
+        let card = if self.config.mask { event.card.masked() } else { event.card.clone() };
+
+        let event_payload = serde_json::json!({
+            "event": event.kind,
+            "time": event.time,
+            "reader_name": event.reader_name,
+            "card": card,
+        });
+
+        self.publish(self.events_topic(), false, serde_json::to_vec(&event_payload).unwrap()).await;
+
+        let current_card = match event.kind {
+            CardEventKind::Inserted => serde_json::to_vec(&card).unwrap(),
+            CardEventKind::Removed => b"null".to_vec(),
+        };
+
+        self.publish(self.reader_card_topic(&event.reader_name), true, current_card).await;
+    }
+}
@@ -0,0 +1,554 @@
+use std::{
+    error::Error,
+    fmt,
+    fmt::{Display, Formatter},
+    num::ParseIntError,
+    string::FromUtf8Error,
+};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{big5::NameDecoding, derived::NHICardDerived};
+
+#[derive(Debug)]
+pub struct NHICardParseError;
+
+impl Display for NHICardParseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("不是正確的健保卡")
+    }
+}
+
+impl Error for NHICardParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NHICardEncodeError(pub &'static str);
+
+impl Display for NHICardEncodeError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Error for NHICardEncodeError {}
+
+impl From<FromUtf8Error> for NHICardParseError {
+    #[inline]
+    fn from(_: FromUtf8Error) -> Self {
+        NHICardParseError
+    }
+}
+
+impl From<ParseIntError> for NHICardParseError {
+    #[inline]
+    fn from(_: ParseIntError) -> Self {
+        NHICardParseError
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Sex {
+    #[serde(rename = "M")]
+    Male,
+    #[serde(rename = "F")]
+    Female,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NHICardBasic {
+    pub reader_name:             Option<String>,
+    pub card_no:                 String,
+    pub full_name:               String,
+    pub id_no:                   String,
+    pub birth_date:              NaiveDate,
+    pub birth_date_timestamp:    i64,
+    pub sex:                     Sex,
+    pub issue_date:              NaiveDate,
+    pub issue_date_timestamp:    i64,
+    /// Generated when the card is inserted, and stable until it is removed.
+    pub session_id:              Option<String>,
+    pub inserted_at:             Option<DateTime<Local>>,
+    /// The Big5 bytes of the name in uppercase hex.
+    pub full_name_raw:           String,
+    /// Whether some characters of the name are replaced with the placeholder.
+    pub name_has_unmapped_chars: bool,
+    #[serde(flatten)]
+    pub derived:                 Option<NHICardDerived>,
+}
+
+#[inline]
+fn mask_chars(s: &str, keep_start: usize, keep_end: usize, mask: char) -> String {
+    let count = s.chars().count();
+
+    s.chars()
+        .enumerate()
+        .map(|(i, c)| if i < keep_start || i + keep_end >= count { c } else { mask })
+        .collect()
+}
+
+/// Midnight may not exist in the local time zone because of a DST transition, in which case the offset of that instant is used.
+#[inline]
+fn local_midnight_timestamp_millis(date: NaiveDate) -> i64 {
+    let midnight = NaiveDateTime::new(date, NaiveTime::default());
+
+    match midnight.and_local_timezone(Local).latest() {
+        Some(date_time) => date_time.timestamp_millis(),
+        None => (midnight - Local.offset_from_utc_datetime(&midnight)).and_utc().timestamp_millis(),
+    }
+}
+
+impl NHICardBasic {
+    /// The serialized field names in a stable order, for tabular output formats.
+    pub const FIELD_NAMES: &'static [&'static str] = &[
+        "reader_name",
+        "card_no",
+        "full_name",
+        "id_no",
+        "birth_date",
+        "birth_date_timestamp",
+        "sex",
+        "issue_date",
+        "issue_date_timestamp",
+        "session_id",
+        "inserted_at",
+        "full_name_raw",
+        "name_has_unmapped_chars",
+    ];
+    /// The length of the raw basic data record.
+    pub const RAW_LENGTH: usize = 57;
+
+    /// Returns a copy with the card number, the name and the ID number partially masked.
+    pub fn masked(&self) -> Self {
+        Self {
+            card_no: mask_chars(&self.card_no, 0, 4, '*'),
+            full_name: mask_chars(&self.full_name, 1, 0, '○'),
+            full_name_raw: mask_chars(&self.full_name_raw, 4, 0, '*'),
+            id_no: mask_chars(&self.id_no, 3, 3, '*'),
+            ..self.clone()
+        }
+    }
+
+    #[inline]
+    pub fn builder() -> NHICardBasicBuilder {
+        NHICardBasicBuilder::default()
+    }
+
+    fn naive_date_to_raw(date: NaiveDate) -> Result<[u8; 7], NHICardEncodeError> {
+        match date.year() - 1911 {
+            tw_year @ 1..=999 => {
+                let s = format!("{tw_year:03}{:02}{:02}", date.month(), date.day());
+
+                Ok(s.as_bytes().try_into().unwrap())
+            },
+            _ => Err(NHICardEncodeError("日期須在民國 1 年到 999 年之間")),
+        }
+    }
+
+    /// Encodes the basic data into the raw record which `from_raw` parses. The reader name and the timestamps are not a part of the record.
+    pub fn to_raw(&self) -> Result<[u8; Self::RAW_LENGTH], NHICardEncodeError> {
+        if self.card_no.len() != 12 || !self.card_no.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(NHICardEncodeError("卡號須為 12 位數字"));
+        }
+
+        if self.id_no.len() != 10 || !self.id_no.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(NHICardEncodeError("身份證字號須為 10 個英數字"));
+        }
+
+        let (full_name, _encoding_used, had_errors) = encoding_rs::BIG5.encode(&self.full_name);
+
+        if had_errors || full_name.len() > 20 || full_name.contains(&0) {
+            return Err(NHICardEncodeError("姓名須為 Big5 編碼後不超過 20 個位元組的文字"));
+        }
+
+        let mut data = [0u8; Self::RAW_LENGTH];
+
+        data[..12].copy_from_slice(self.card_no.as_bytes());
+        data[12..12 + full_name.len()].copy_from_slice(&full_name);
+        data[32..42].copy_from_slice(self.id_no.as_bytes());
+        data[42..49].copy_from_slice(&Self::naive_date_to_raw(self.birth_date)?);
+        data[49] = match self.sex {
+            Sex::Male => b'M',
+            Sex::Female => b'F',
+        };
+        data[50..57].copy_from_slice(&Self::naive_date_to_raw(self.issue_date)?);
+
+        Ok(data)
+    }
+
+    /// Parses a ROC date in the `YYYMMDD` format.
+    pub fn raw_to_naive_date(data: &[u8]) -> Result<NaiveDate, NHICardParseError> {
+        let s = String::from_utf8(data.to_vec())?;
+
+        let year = {
+            let tw_year = s.chars().take(3).collect::<String>().parse::<i32>()?;
+
+            1911 + tw_year
+        };
+
+        let month = s.chars().skip(3).take(2).collect::<String>().parse::<u32>()?;
+        let date = s.chars().skip(5).take(2).collect::<String>().parse::<u32>()?;
+
+        match NaiveDate::from_ymd_opt(year, month, date) {
+            Some(date) => Ok(date),
+            None => Err(NHICardParseError),
+        }
+    }
+
+    /// Parses the raw record strictly, i.e. the name must be in Big5-HKSCS.
+    #[inline]
+    pub fn from_raw<D: AsRef<[u8]>>(data: D) -> Result<Self, NHICardParseError> {
+        Self::from_raw_with_name_decoding(data, &NameDecoding::default())
+    }
+
+    pub fn from_raw_with_name_decoding<D: AsRef<[u8]>>(
+        data: D,
+        name_decoding: &NameDecoding,
+    ) -> Result<Self, NHICardParseError> {
+        let data = data.as_ref();
+
+        if data.len() < Self::RAW_LENGTH {
+            return Err(NHICardParseError);
+        }
+
+        let card_no = String::from_utf8(data[..12].to_vec())?;
+
+        let full_name_raw = {
+            let s = 12usize;
+            let mut e = s;
+
+            while e < 32 {
+                if data[e] == 0 {
+                    break;
+                }
+
+                e += 1;
+            }
+
+            &data[s..e]
+        };
+
+        let (full_name, name_has_unmapped_chars) =
+            name_decoding.decode(full_name_raw).ok_or(NHICardParseError)?;
+
+        let id_no = String::from_utf8(data[32..42].to_vec())?;
+
+        let birth_date = Self::raw_to_naive_date(&data[42..49])?;
+
+        let sex = match data[49] {
+            b'M' => Sex::Male,
+            b'F' => Sex::Female,
+            _ => {
+                return Err(NHICardParseError);
+            },
+        };
+
+        let issue_date = Self::raw_to_naive_date(&data[50..57])?;
+
+        Ok(Self {
+            reader_name: None,
+            card_no,
+            full_name,
+            full_name_raw: hex::encode_upper(full_name_raw),
+            name_has_unmapped_chars,
+            id_no,
+            birth_date,
+            birth_date_timestamp: local_midnight_timestamp_millis(birth_date),
+            sex,
+            issue_date,
+            issue_date_timestamp: local_midnight_timestamp_millis(issue_date),
+            session_id: None,
+            inserted_at: None,
+            derived: None,
+        })
+    }
+}
+
+/// Builds a card, or its raw record, from typed fields. Every field has a fixed default so that test fixtures are deterministic.
+#[derive(Debug, Clone)]
+pub struct NHICardBasicBuilder {
+    card_no:    String,
+    full_name:  String,
+    id_no:      String,
+    birth_date: NaiveDate,
+    sex:        Sex,
+    issue_date: NaiveDate,
+}
+
+impl Default for NHICardBasicBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            card_no:    String::from("000000000000"),
+            full_name:  String::from("測試"),
+            id_no:      String::from("A123456789"),
+            birth_date: NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+            sex:        Sex::Male,
+            issue_date: NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        }
+    }
+}
+
+impl NHICardBasicBuilder {
+    #[inline]
+    pub fn card_no<S: Into<String>>(mut self, card_no: S) -> Self {
+        self.card_no = card_no.into();
+        self
+    }
+
+    #[inline]
+    pub fn full_name<S: Into<String>>(mut self, full_name: S) -> Self {
+        self.full_name = full_name.into();
+        self
+    }
+
+    #[inline]
+    pub fn id_no<S: Into<String>>(mut self, id_no: S) -> Self {
+        self.id_no = id_no.into();
+        self
+    }
+
+    #[inline]
+    pub fn birth_date(mut self, birth_date: NaiveDate) -> Self {
+        self.birth_date = birth_date;
+        self
+    }
+
+    #[inline]
+    pub fn sex(mut self, sex: Sex) -> Self {
+        self.sex = sex;
+        self
+    }
+
+    #[inline]
+    pub fn issue_date(mut self, issue_date: NaiveDate) -> Self {
+        self.issue_date = issue_date;
+        self
+    }
+
+    pub fn build_raw(&self) -> Result<[u8; NHICardBasic::RAW_LENGTH], NHICardEncodeError> {
+        NHICardBasic {
+            reader_name:             None,
+            card_no:                 self.card_no.clone(),
+            full_name:               self.full_name.clone(),
+            full_name_raw:           String::new(),
+            name_has_unmapped_chars: false,
+            id_no:                   self.id_no.clone(),
+            birth_date:              self.birth_date,
+            birth_date_timestamp:    0,
+            sex:                     self.sex,
+            issue_date:              self.issue_date,
+            issue_date_timestamp:    0,
+            session_id:              None,
+            inserted_at:             None,
+            derived:                 None,
+        }
+        .to_raw()
+    }
+
+    /// Builds the card by parsing the encoded record, so the timestamps are the same as a real card's.
+    #[inline]
+    pub fn build(&self) -> Result<NHICardBasic, NHICardEncodeError> {
+        Ok(NHICardBasic::from_raw(self.build_raw()?).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::big5::Big5Extensions;
+
+    const NAME_CHARS: &[char] = &[
+        '王', '李', '張', '劉', '陳', '楊', '黃', '趙', '吳', '周', '林', '蔡', '小', '明', '美',
+        '麗', '志', '豪', '淑', '芬', '怡', '君', '家', '瑋', 'a', 'B', ' ', '-',
+    ];
+
+    fn date_strategy() -> impl Strategy<Value = NaiveDate> {
+        let min = NaiveDate::from_ymd_opt(1912, 1, 1).unwrap().num_days_from_ce();
+        let max = NaiveDate::from_ymd_opt(2910, 12, 31).unwrap().num_days_from_ce();
+
+        (min..=max).prop_map(|days| NaiveDate::from_num_days_from_ce_opt(days).unwrap())
+    }
+
+    fn builder_strategy() -> impl Strategy<Value = NHICardBasicBuilder> {
+        (
+            "[0-9]{12}",
+            prop::collection::vec(prop::sample::select(NAME_CHARS), 0..=10),
+            "[A-Z][12][0-9]{8}",
+            date_strategy(),
+            prop_oneof![Just(Sex::Male), Just(Sex::Female)],
+            date_strategy(),
+        )
+            .prop_map(|(card_no, full_name, id_no, birth_date, sex, issue_date)| {
+                NHICardBasic::builder()
+                    .card_no(card_no)
+                    .full_name(full_name.into_iter().collect::<String>())
+                    .id_no(id_no)
+                    .birth_date(birth_date)
+                    .sex(sex)
+                    .issue_date(issue_date)
+            })
+    }
+
+    #[test]
+    fn known_record() {
+        let raw = b"000012345678\xA4\xFD\xA4\x70\xA9\xFA\0\0\0\0\0\0\0\0\0\0\0\0\0\0A1234567890800102M1100505";
+
+        let card = NHICardBasic::from_raw(raw).unwrap();
+
+        assert_eq!("000012345678", card.card_no);
+        assert_eq!("王小明", card.full_name);
+        assert_eq!("A123456789", card.id_no);
+        assert_eq!(NaiveDate::from_ymd_opt(1991, 1, 2).unwrap(), card.birth_date);
+        assert_eq!(Sex::Male, card.sex);
+        assert_eq!(NaiveDate::from_ymd_opt(2021, 5, 5).unwrap(), card.issue_date);
+        assert_eq!(local_midnight_timestamp_millis(card.birth_date), card.birth_date_timestamp);
+        assert_eq!(local_midnight_timestamp_millis(card.issue_date), card.issue_date_timestamp);
+
+        assert_eq!(raw, &card.to_raw().unwrap());
+    }
+
+    #[test]
+    fn issue_date_timestamp() {
+        let raw = b"000012345678\xA4\xFD\xA4\x70\xA9\xFA\0\0\0\0\0\0\0\0\0\0\0\0\0\0A1234567890800102M1100505";
+
+        let card = NHICardBasic::from_raw(raw).unwrap();
+
+        let issue_date = NaiveDate::from_ymd_opt(2021, 5, 5).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        // it was the timestamp of the birth date
+        assert_ne!(card.birth_date_timestamp, card.issue_date_timestamp);
+        assert_eq!(
+            issue_date.and_local_timezone(Local).unwrap().timestamp_millis(),
+            card.issue_date_timestamp
+        );
+
+        // 11,081 days from 1991-01-02 to 2021-05-05, give or take a daylight saving hour
+        let difference = card.issue_date_timestamp - card.birth_date_timestamp;
+
+        assert!((difference - 11_081 * 86_400_000).abs() <= 3_600_000);
+    }
+
+    #[test]
+    fn unmapped_name_chars() {
+        // 王 followed by an unassigned code, which is neither in HKSCS nor in the user-defined areas
+        let raw = b"000012345678\xA4\xFD\xA3\xF0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0A1234567890800102M1100505";
+
+        assert!(NHICardBasic::from_raw(raw).is_err());
+
+        let lenient = NameDecoding {
+            lenient: true,
+            ..NameDecoding::default()
+        };
+
+        let card = NHICardBasic::from_raw_with_name_decoding(raw, &lenient).unwrap();
+
+        assert_eq!("王\u{FFFD}", card.full_name);
+        assert_eq!("A4FDA3F0", card.full_name_raw);
+        assert!(card.name_has_unmapped_chars);
+
+        let mapped = NameDecoding {
+            lenient:    false,
+            extensions: Big5Extensions::parse("# comment\n\nA3F0 U+2420E\n").unwrap(),
+        };
+
+        let card = NHICardBasic::from_raw_with_name_decoding(raw, &mapped).unwrap();
+
+        assert_eq!("王\u{2420E}", card.full_name);
+        assert!(!card.name_has_unmapped_chars);
+
+        assert_eq!(Some(2), Big5Extensions::parse("FA40 U+2420E\nFA41").err().map(|e| e.line));
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert!(NHICardBasic::builder().card_no("12345").build_raw().is_err());
+        assert!(NHICardBasic::builder().id_no("A12345678X0").build_raw().is_err());
+        assert!(NHICardBasic::builder().full_name("測試測試測試測試測試測").build_raw().is_err());
+        assert!(NHICardBasic::builder().full_name("😀").build_raw().is_err());
+        assert!(NHICardBasic::builder()
+            .birth_date(NaiveDate::from_ymd_opt(1911, 12, 31).unwrap())
+            .build_raw()
+            .is_err());
+    }
+
+    #[test]
+    fn derived_fields() {
+        let card = NHICardBasic::builder()
+            .birth_date(NaiveDate::from_ymd_opt(1991, 1, 2).unwrap())
+            .issue_date(NaiveDate::from_ymd_opt(2021, 5, 5).unwrap())
+            .build()
+            .unwrap();
+
+        let derived = card.derive(NaiveDate::from_ymd_opt(2026, 1, 1).unwrap());
+
+        assert_eq!(34, derived.age_years);
+        assert_eq!(11, derived.age_months);
+        assert_eq!("0800102", derived.birth_date_roc);
+        assert_eq!("民國 080 年 01 月 02 日", derived.birth_date_roc_text);
+        assert_eq!("1100505", derived.issue_date_roc);
+        assert_eq!("民國 110 年 05 月 05 日", derived.issue_date_roc_text);
+        assert_eq!(4, derived.card_age_years);
+        assert_eq!(7, derived.card_age_months);
+
+        let derived = card.derive(NaiveDate::from_ymd_opt(2026, 1, 2).unwrap());
+
+        assert_eq!(35, derived.age_years);
+        assert_eq!(0, derived.age_months);
+
+        let derived = card.derive(NaiveDate::from_ymd_opt(1990, 1, 1).unwrap());
+
+        assert_eq!(0, derived.age_years);
+        assert_eq!(0, derived.age_months);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let card = NHICardBasic::builder().build().unwrap();
+
+        let deserialized: NHICardBasic =
+            serde_json::from_str(&serde_json::to_string(&card).unwrap()).unwrap();
+
+        assert_eq!(card.card_no, deserialized.card_no);
+        assert_eq!(card.birth_date, deserialized.birth_date);
+        assert!(deserialized.derived.is_none());
+
+        let card = card.with_derived();
+
+        let deserialized: NHICardBasic =
+            serde_json::from_str(&serde_json::to_string(&card).unwrap()).unwrap();
+
+        assert_eq!(card.derived, deserialized.derived);
+    }
+
+    proptest! {
+        #[test]
+        fn raw_round_trip(builder in builder_strategy()) {
+            let raw = builder.build_raw().unwrap();
+            let card = NHICardBasic::from_raw(raw).unwrap();
+
+            prop_assert_eq!(&builder.card_no, &card.card_no);
+            prop_assert_eq!(&builder.full_name, &card.full_name);
+            prop_assert_eq!(&builder.id_no, &card.id_no);
+            prop_assert_eq!(builder.birth_date, card.birth_date);
+            prop_assert_eq!(builder.sex, card.sex);
+            prop_assert_eq!(builder.issue_date, card.issue_date);
+            prop_assert_eq!(local_midnight_timestamp_millis(builder.birth_date), card.birth_date_timestamp);
+            prop_assert_eq!(local_midnight_timestamp_millis(builder.issue_date), card.issue_date_timestamp);
+
+            prop_assert_eq!(raw, card.to_raw().unwrap());
+        }
+
+        #[test]
+        fn trailing_status_word(builder in builder_strategy()) {
+            let mut response = builder.build_raw().unwrap().to_vec();
+            response.extend_from_slice(&[0x90, 0x00]);
+
+            prop_assert_eq!(builder.build().unwrap().to_raw(), NHICardBasic::from_raw(response).unwrap().to_raw());
+        }
+    }
+}
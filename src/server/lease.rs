@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::prelude::*;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::card::{CardRecord, NHICardBasic};
+
+/// Leases by reader names.
+static LEASES: Lazy<Mutex<HashMap<String, Lease>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug)]
+struct Lease {
+    id:         String,
+    /// The WebSocket connection which holds the lease, if any.
+    owner:      Option<u64>,
+    expires_at: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaseInfo {
+    pub lease_id:   String,
+    pub reader:     String,
+    pub expires_at: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseError {
+    /// The reader has been reserved by another client.
+    Reserved,
+    /// The lease does not exist or has expired.
+    NotFound,
+}
+
+impl Display for LeaseError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reserved => f.write_str("讀卡機已被其它客戶端保留"),
+            Self::NotFound => f.write_str("保留不存在或已過期"),
+        }
+    }
+}
+
+impl Error for LeaseError {}
+
+/// Who is asking for card data. Clients see the readers leased by others as busy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LeaseHolder<'a> {
+    pub lease_ids: &'a [String],
+    pub owner:     Option<u64>,
+}
+
+impl LeaseHolder<'_> {
+    #[inline]
+    fn holds(&self, lease: &Lease) -> bool {
+        self.lease_ids.contains(&lease.id) || (self.owner.is_some() && self.owner == lease.owner)
+    }
+}
+
+#[inline]
+fn leases() -> MutexGuard<'static, HashMap<String, Lease>> {
+    let mut leases = LEASES.lock().unwrap();
+
+    let now = Local::now();
+
+    leases.retain(|_, lease| lease.expires_at > now);
+
+    leases
+}
+
+/// The upper bound of `--lease-ttl`.
+pub const MAX_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[inline]
+fn expires_at(ttl: Duration) -> DateTime<Local> {
+    let now = Local::now();
+
+    let ttl = chrono::Duration::seconds(ttl.as_secs().min(MAX_TTL_SECS) as i64);
+
+    now.checked_add_signed(ttl).unwrap_or(now)
+}
+
+/// Reserves a reader for `ttl`. A client which already holds the lease of the reader gets it renewed.
+pub fn acquire(
+    reader: &str,
+    ttl: Duration,
+    holder: LeaseHolder<'_>,
+) -> Result<LeaseInfo, LeaseError> {
+    let mut leases = leases();
+
+    if let Some(lease) = leases.get(reader) {
+        if !holder.holds(lease) {
+            return Err(LeaseError::Reserved);
+        }
+    }
+
+    let lease = leases.entry(String::from(reader)).or_insert_with(|| {
+        let lease = Lease {
+            id:         Uuid::new_v4().to_string(),
+            owner:      holder.owner,
+            expires_at: Local::now(),
+        };
+
+        tracing::debug!(target: "lease", reader, lease_id = lease.id, "acquire");
+
+        lease
+    });
+
+    lease.expires_at = expires_at(ttl);
+
+    Ok(LeaseInfo {
+        lease_id:   lease.id.clone(),
+        reader:     String::from(reader),
+        expires_at: lease.expires_at,
+    })
+}
+
+/// Extends the lease by `ttl` from now.
+pub fn renew(lease_id: &str, ttl: Duration) -> Result<LeaseInfo, LeaseError> {
+    let mut leases = leases();
+
+    let (reader, lease) =
+        leases.iter_mut().find(|(_, lease)| lease.id == lease_id).ok_or(LeaseError::NotFound)?;
+
+    lease.expires_at = expires_at(ttl);
+
+    Ok(LeaseInfo {
+        lease_id:   lease.id.clone(),
+        reader:     reader.clone(),
+        expires_at: lease.expires_at,
+    })
+}
+
+pub fn release(lease_id: &str) -> Result<(), LeaseError> {
+    let mut leases = leases();
+
+    let len = leases.len();
+
+    leases.retain(|_, lease| lease.id != lease_id);
+
+    if leases.len() < len {
+        tracing::debug!(target: "lease", lease_id, "release");
+
+        Ok(())
+    } else {
+        Err(LeaseError::NotFound)
+    }
+}
+
+/// Releases the leases held by a WebSocket connection when it is closed.
+#[inline]
+pub fn release_owner(owner: u64) {
+    LEASES.lock().unwrap().retain(|_, lease| lease.owner != Some(owner));
+}
+
+/// Whether the reader has been reserved by a client other than `holder`.
+#[inline]
+pub fn is_reserved_for_others(reader: &str, holder: LeaseHolder<'_>) -> bool {
+    leases().get(reader).is_some_and(|lease| !holder.holds(lease))
+}
+
+/// Whether the reader has been reserved by any client. The card events of such a reader are not sent to the webhooks, MQTT, MLLP, the virtual keyboard or the clipboard.
+#[inline]
+pub fn is_reserved(reader: &str) -> bool {
+    is_reserved_for_others(reader, LeaseHolder::default())
+}
+
+/// A card which is in a reader.
+pub trait ReaderCard {
+    fn reader_name(&self) -> Option<&str>;
+}
+
+impl ReaderCard for NHICardBasic {
+    #[inline]
+    fn reader_name(&self) -> Option<&str> {
+        self.reader_name.as_deref()
+    }
+}
+
+impl ReaderCard for CardRecord {
+    #[inline]
+    fn reader_name(&self) -> Option<&str> {
+        CardRecord::reader_name(self)
+    }
+}
+
+/// Removes the cards in the readers reserved by clients other than `holder`.
+pub fn retain_visible<T: ReaderCard>(cards: &mut Vec<T>, holder: LeaseHolder<'_>) {
+    let leases = leases();
+
+    if leases.is_empty() {
+        return;
+    }
+
+    cards.retain(|card| match card.reader_name().and_then(|reader| leases.get(reader)) {
+        Some(lease) => holder.holds(lease),
+        None => true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    struct TestCard(&'static str);
+
+    impl ReaderCard for TestCard {
+        #[inline]
+        fn reader_name(&self) -> Option<&str> {
+            Some(self.0)
+        }
+    }
+
+    #[inline]
+    fn owner(id: u64) -> LeaseHolder<'static> {
+        LeaseHolder {
+            lease_ids: &[], owner: Some(id)
+        }
+    }
+
+    #[test]
+    fn acquire_and_renew() {
+        let reader = "Lease Reader 1";
+
+        let lease = acquire(reader, TTL, owner(1)).unwrap();
+
+        assert_eq!(reader, lease.reader);
+        assert_eq!(Err(LeaseError::Reserved), acquire(reader, TTL, owner(2)).map(|_| ()));
+        assert_eq!(
+            Err(LeaseError::Reserved),
+            acquire(reader, TTL, LeaseHolder::default()).map(|_| ())
+        );
+
+        // the holder gets the same lease renewed
+        assert_eq!(lease.lease_id, acquire(reader, TTL, owner(1)).unwrap().lease_id);
+
+        let lease_ids = [lease.lease_id.clone()];
+
+        let renewed = acquire(reader, TTL, LeaseHolder {
+            lease_ids: &lease_ids, owner: None
+        })
+        .unwrap();
+
+        assert_eq!(lease.lease_id, renewed.lease_id);
+        assert!(renewed.expires_at >= lease.expires_at);
+
+        let renewed = renew(&lease.lease_id, TTL).unwrap();
+
+        assert_eq!(reader, renewed.reader);
+        assert_eq!(Err(LeaseError::NotFound), renew("unknown", TTL).map(|_| ()));
+
+        release(&lease.lease_id).unwrap();
+    }
+
+    #[test]
+    fn release_lease() {
+        let reader = "Lease Reader 2";
+
+        let lease = acquire(reader, TTL, owner(3)).unwrap();
+
+        assert!(is_reserved(reader));
+
+        assert_eq!(Ok(()), release(&lease.lease_id));
+        assert_eq!(Err(LeaseError::NotFound), release(&lease.lease_id));
+        assert!(!is_reserved(reader));
+
+        acquire(reader, TTL, owner(3)).unwrap();
+
+        release_owner(3);
+
+        assert!(!is_reserved(reader));
+    }
+
+    #[test]
+    fn expiry() {
+        let reader = "Lease Reader 3";
+
+        let lease = acquire(reader, Duration::ZERO, owner(4)).unwrap();
+
+        assert!(!is_reserved(reader));
+        assert_eq!(Err(LeaseError::NotFound), renew(&lease.lease_id, TTL).map(|_| ()));
+
+        // another client can reserve the reader after the lease expires
+        acquire(reader, TTL, owner(5)).unwrap();
+
+        release_owner(5);
+    }
+
+    #[test]
+    fn ttl_bound() {
+        let expires_at = expires_at(Duration::from_secs(u64::MAX));
+
+        assert!(expires_at <= Local::now() + chrono::Duration::seconds(MAX_TTL_SECS as i64));
+        assert!(expires_at > Local::now() + chrono::Duration::seconds(MAX_TTL_SECS as i64 - 60));
+    }
+
+    #[test]
+    fn visible_cards() {
+        let reader = "Lease Reader 4";
+
+        let lease = acquire(reader, TTL, owner(6)).unwrap();
+
+        let lease_ids = [lease.lease_id.clone()];
+
+        let holders = [
+            (owner(6), 2),
+            (owner(7), 1),
+            (LeaseHolder::default(), 1),
+            (
+                LeaseHolder {
+                    lease_ids: &lease_ids, owner: None
+                },
+                2,
+            ),
+        ];
+
+        for (holder, count) in holders {
+            let mut cards = vec![TestCard(reader), TestCard("Lease Reader 5")];
+
+            retain_visible(&mut cards, holder);
+
+            assert_eq!(count, cards.len());
+
+            assert!(is_reserved_for_others(reader, holder) == (count == 1));
+        }
+
+        release_owner(6);
+    }
+}
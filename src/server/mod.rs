@@ -1,25 +1,38 @@
+mod lease;
+
 use std::{
+    borrow::Cow,
+    fmt::Display,
     io,
     io::IsTerminal,
     net::SocketAddr,
     sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc, Once,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
     },
     time::{Duration, Instant, SystemTime},
 };
 
 use axum::{
-    extract::{ws::Message, Query, State, WebSocketUpgrade},
-    http::{header, HeaderValue},
-    response::IntoResponse,
-    routing::get,
-    Router,
+    extract::{
+        ws::{CloseFrame, Message},
+        ConnectInfo, MatchedPath, Path, Query, Request, State, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use serde::Deserialize;
+use once_cell::sync::Lazy;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use serde_json::json;
-use tokio::{sync::mpsc, task, time};
+use subtle::ConstantTimeEq;
+use tokio::{
+    sync::{mpsc, Mutex},
+    task, time,
+};
 use tower_http::{
     cors::CorsLayer,
     set_header::SetResponseHeaderLayer,
@@ -27,28 +40,150 @@ use tower_http::{
 };
 use tracing::Level;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tw_nhi_icc_service::{ErrorResponse, ServiceVersion};
 
-use crate::card::*;
+pub use self::lease::{is_reserved as is_reader_reserved, MAX_TTL_SECS as MAX_LEASE_TTL_SECS};
+use self::lease::{LeaseError, LeaseHolder, LeaseInfo};
+use crate::{
+    audit::{AuditClient, AuditEventKind, AuditLog, AuditQuery},
+    card::*,
+    format::{hl7, OutputFormat},
+    metrics,
+};
 
 static WS_COUNTER: AtomicU64 = AtomicU64::new(0);
+static WS_ACTIVE_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-static mut VERSION: String = String::new();
-
-const PING_INTERVAL_SECONDS: u64 = 25;
-const PING_PONG_DELAY_TIMEOUT_SECONDS: u64 = 10;
+static VERSION: Lazy<String> = Lazy::new(|| {
+    serde_json::to_string(&ServiceVersion {
+        major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
+        minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
+        patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
+        pre:   String::from(env!("CARGO_PKG_VERSION_PRE")),
+        text:  String::from(env!("CARGO_PKG_VERSION")),
+    })
+    .unwrap()
+});
 
-static PING_INTERVAL: Duration = Duration::from_secs(PING_INTERVAL_SECONDS);
-static PONG_INTERVAL: Duration =
-    Duration::from_secs(PING_INTERVAL_SECONDS + PING_PONG_DELAY_TIMEOUT_SECONDS);
+const CLOSE_CODE_TRY_AGAIN_LATER: u16 = 1013;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub default_card_fetch_interval: u64,
+    pub ws_ping_interval:            Duration,
+    pub ws_pong_timeout:             Duration,
+    pub ws_max_connections:          usize,
+    pub admin_token:                 Option<String>,
+    pub audit_log:                   Option<Arc<AuditLog>>,
+    pub simulator:                   Option<Arc<Simulator>>,
+    pub lease_ttl:                   Duration,
+    /// The APDU passthrough is disabled if it is empty.
+    pub apdu_allowlist:              Arc<[ApduPattern]>,
+}
+
+struct WSActiveGuard;
+
+impl WSActiveGuard {
+    fn acquire(max_connections: usize) -> Option<Self> {
+        let mut current = WS_ACTIVE_COUNTER.load(Ordering::Relaxed);
+
+        loop {
+            if max_connections > 0 && current >= max_connections {
+                return None;
+            }
+
+            match WS_ACTIVE_COUNTER.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    metrics::WS_SESSIONS_ACTIVE.inc();
+
+                    return Some(Self);
+                },
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+impl Drop for WSActiveGuard {
+    #[inline]
+    fn drop(&mut self) {
+        WS_ACTIVE_COUNTER.fetch_sub(1, Ordering::AcqRel);
+
+        metrics::WS_SESSIONS_ACTIVE.dec();
+    }
 }
 
 #[derive(Deserialize)]
 struct WSQuery {
     interval: Option<u64>,
+    #[serde(default)]
+    format:   OutputFormat,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    lease:    Vec<String>,
+}
+
+/// Parses a query flag such as `fresh=1`.
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    match String::deserialize(deserializer)?.as_str() {
+        "1" | "true" => Ok(true),
+        "" | "0" | "false" => Ok(false),
+        s => Err(de::Error::invalid_value(de::Unexpected::Str(s), &"1 or 0")),
+    }
+}
+
+/// Parses a comma-separated query value such as `lease=a,b`.
+fn deserialize_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(String::deserialize(deserializer)?
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+fn audit_client(addr: SocketAddr, headers: &HeaderMap, endpoint: &str) -> AuditClient {
+    AuditClient {
+        client_ip: Some(addr.ip()),
+        api_key:   headers.get("x-api-key").and_then(|v| v.to_str().ok()).map(String::from),
+        endpoint:  Some(String::from(endpoint)),
+    }
+}
+
+fn audit_reads(audit_log: &AuditLog, cards: &[NHICardBasic], client: &AuditClient) {
+    for card in cards {
+        audit_log.record(AuditEventKind::Read, card.reader_name.clone(), &card.card_no, client);
+    }
+}
+
+fn check_admin_token(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Some(admin_token) = state.admin_token.as_deref() else {
+        return Err(StatusCode::FORBIDDEN);
+    };
+
+    match headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        Some(token) if is_same_token(token, admin_token) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Compares the tokens in constant time, so that the admin token cannot be guessed byte by byte from the response time.
+#[inline]
+fn is_same_token(token: &str, admin_token: &str) -> bool {
+    token.as_bytes().ct_eq(admin_token.as_bytes()).into()
+}
+
+/// Checks the token of an `auth` action, since browsers cannot set the `Authorization` header of a WebSocket.
+#[inline]
+fn is_admin_token(state: &AppState, token: &str) -> bool {
+    state.admin_token.as_deref().is_some_and(|admin_token| is_same_token(token, admin_token))
 }
 
 #[inline]
@@ -56,24 +191,129 @@ fn now() -> u64 {
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64
 }
 
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum WSAction {
+    Auth { token: String },
+    Reserve { reader: String },
+    Renew { lease_id: String },
+    Release { lease_id: String },
+    Apdu { reader: String, apdus: Vec<String> },
+}
+
+#[inline]
+fn error_json<E: Display>(error: E) -> String {
+    serde_json::to_string(&ErrorResponse {
+        error: error.to_string()
+    })
+    .unwrap()
+}
+
+/// Returns the JSON reply of an action sent via WebSocket.
+async fn handle_ws_action(
+    action: WSAction,
+    id: u64,
+    state: &AppState,
+    holder: LeaseHolder<'_>,
+    client: &AuditClient,
+    admin_authorized: &mut bool,
+) -> String {
+    let result = match action {
+        WSAction::Auth {
+            token,
+        } => {
+            if !is_admin_token(state, &token) {
+                return error_json("未授權");
+            }
+
+            *admin_authorized = true;
+
+            return json!({ "authorized": true }).to_string();
+        },
+        WSAction::Reserve {
+            reader,
+        } => lease::acquire(&reader, state.lease_ttl, LeaseHolder {
+            lease_ids: &[],
+            owner:     Some(id),
+        })
+        .map(Some),
+        WSAction::Renew {
+            lease_id,
+        } => lease::renew(&lease_id, state.lease_ttl).map(Some),
+        WSAction::Release {
+            lease_id,
+        } => lease::release(&lease_id).map(|_| None),
+        WSAction::Apdu {
+            reader,
+            apdus,
+        } => {
+            if !*admin_authorized || state.apdu_allowlist.is_empty() {
+                return error_json("未授權");
+            }
+
+            if lease::is_reserved_for_others(&reader, holder) {
+                return error_json(LeaseError::Reserved);
+            }
+
+            return match passthrough(state, reader, &apdus, client).await {
+                Ok(response) => serde_json::to_string(&response).unwrap(),
+                Err(error) => error_json(error),
+            };
+        },
+    };
+
+    match result {
+        Ok(lease) => json!({ "lease": lease }).to_string(),
+        Err(error) => error_json(error),
+    }
+}
+
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Query(WSQuery {
         interval,
+        format,
+        lease: lease_ids,
     }): Query<WSQuery>,
 ) -> impl IntoResponse {
+    let client = audit_client(addr, &headers, "/ws");
+
     let card_fetch_interval =
         Arc::new(AtomicU64::new(interval.unwrap_or(state.default_card_fetch_interval)));
 
-    ws.on_upgrade(|socket| async move {
+    let mut admin_authorized = check_admin_token(&state, &headers).is_ok();
+
+    let ping_interval = state.ws_ping_interval;
+    let pong_interval = state.ws_ping_interval + state.ws_pong_timeout;
+
+    ws.on_upgrade(move |mut socket| async move {
         let id = WS_COUNTER.fetch_add(1, Ordering::Relaxed);
 
+        let Some(active_guard) = WSActiveGuard::acquire(state.ws_max_connections) else {
+            tracing::warn!(target: "websocket", id, "已達連線數上限");
+
+            let _ = socket
+                .send(Message::Close(Some(CloseFrame {
+                    code:   CLOSE_CODE_TRY_AGAIN_LATER,
+                    reason: Cow::Borrowed("too many connections"),
+                })))
+                .await;
+
+            return;
+        };
+
         tracing::info!(target: "websocket", id, "連線建立");
 
         let card_fetch_interval_sender = card_fetch_interval.clone();
 
-        let (mut sender, mut receiver) = socket.split();
+        let (sender, mut receiver) = socket.split();
+
+        // replies to lease actions are sent along with the card data
+        let sender = Arc::new(Mutex::new(sender));
+        let sender_reply = sender.clone();
 
         let (sender_ctrl, mut receiver_ctrl) = mpsc::channel::<()>(1);
 
@@ -84,23 +324,43 @@ async fn ws_handler(
         let last_message_time_sender = last_message_time.clone();
         let last_message_time_pong = last_message_time.clone();
 
+        let audit_log = state.audit_log.clone();
+
+        let lease_ids_action = lease_ids.clone();
+        let client_action = client.clone();
+
         let t_sender = task::spawn(async move {
+            let mut audited_cards: Vec<(Option<String>, String)> = Vec::new();
+
             'outer: loop {
                 let t = Instant::now();
 
-                let json_string =
-                    match time::timeout(PING_INTERVAL, fetch_nhi_cards_json_string()).await {
-                        Ok(result) => result.unwrap_or_else(|_| String::from("[]")),
-                        Err(_) => {
-                            tracing::warn!(target: "websocket", id, "卡片讀取逾時！");
+                // a slow reader only drops its own card, since each reader is read with the reader timeout
+                let mut cards = fetch_nhi_cards(false).await.unwrap_or_default();
+
+                lease::retain_visible(&mut cards, LeaseHolder {
+                    lease_ids: &lease_ids,
+                    owner:     Some(id),
+                });
 
-                            String::from("[]")
-                        },
-                    };
+                if let Some(audit_log) = audit_log.as_ref() {
+                    let current_cards = cards
+                        .iter()
+                        .map(|card| (card.reader_name.clone(), card.card_no.clone()))
+                        .collect::<Vec<_>>();
 
-                tracing::debug!(target: "websocket", id, "send {json_string:?}");
+                    if current_cards != audited_cards {
+                        audit_reads(audit_log, &cards, &client);
 
-                match sender.send(Message::Text(json_string)).await {
+                        audited_cards = current_cards;
+                    }
+                }
+
+                let text = format.render(&cards);
+
+                tracing::debug!(target: "websocket", id, "send {text:?}");
+
+                match sender.lock().await.send(Message::Text(text)).await {
                     Ok(_) => last_message_time_sender.store(now(), Ordering::Relaxed),
                     Err(error) => {
                         tracing::info!(target: "websocket", id, ?error);
@@ -124,16 +384,16 @@ async fn ws_handler(
 
                     let sleep_interval = card_fetch_interval - d;
 
-                    if sleep_interval <= PING_INTERVAL {
+                    if sleep_interval <= ping_interval {
                         time::sleep(sleep_interval).await;
 
                         break;
                     } else {
-                        time::sleep(PING_INTERVAL).await;
+                        time::sleep(ping_interval).await;
 
                         tracing::debug!(target: "websocket", id, "send ping");
 
-                        match sender.send(Message::Ping(vec![1, 2, 3])).await {
+                        match sender.lock().await.send(Message::Ping(vec![1, 2, 3])).await {
                             Ok(_) => last_message_time_sender.store(now(), Ordering::Relaxed),
                             Err(error) => {
                                 tracing::info!(target: "websocket", id, ?error);
@@ -150,11 +410,11 @@ async fn ws_handler(
 
         let t_pong = task::spawn(async move {
             loop {
-                time::sleep(PONG_INTERVAL).await;
+                time::sleep(pong_interval).await;
 
                 let last_pong = last_message_time_pong.load(Ordering::Relaxed);
 
-                if now() - last_pong > PONG_INTERVAL.as_millis() as u64 {
+                if now() - last_pong > pong_interval.as_millis() as u64 {
                     tracing::info!(target: "websocket", id, "客戶端沒有回應");
 
                     sender_ctrl_pong.send(()).await.unwrap();
@@ -187,6 +447,17 @@ async fn ws_handler(
                                         break;
                                     } else if let Ok(seconds) = s.parse::<u64>() {
                                         card_fetch_interval.store(seconds, Ordering::Relaxed);
+                                    } else if let Ok(action) = serde_json::from_str::<WSAction>(&s) {
+                                        let holder = LeaseHolder {
+                                            lease_ids: &lease_ids_action,
+                                            owner:     Some(id),
+                                        };
+
+                                        let reply = handle_ws_action(action, id, &state, holder, &client_action, &mut admin_authorized).await;
+
+                                        if sender_reply.lock().await.send(Message::Text(reply)).await.is_err() {
+                                            break;
+                                        }
                                     }
                                 },
                                 _ => (),
@@ -207,40 +478,531 @@ async fn ws_handler(
         t_sender.abort();
         t_pong.abort();
 
+        lease::release_owner(id);
+
+        drop(active_guard);
+
         tracing::info!(target: "websocket", id, "連線結束");
     })
 }
 
-pub async fn index_handler() -> impl IntoResponse {
-    let json_string = fetch_nhi_cards_json_string().await.unwrap_or_else(|_| String::from("[]"));
+#[derive(Deserialize)]
+pub struct IndexQuery {
+    format: Option<OutputFormat>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    fresh:  bool,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    lease:  Vec<String>,
+}
+
+pub async fn index_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(IndexQuery {
+        format,
+        fresh,
+        lease,
+    }): Query<IndexQuery>,
+) -> impl IntoResponse {
+    let format = match format {
+        Some(format) => format,
+        None => match headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) {
+            Some(accept) if !accept.trim().is_empty() => match OutputFormat::from_accept(accept) {
+                Some(format) => format,
+                None => {
+                    return (
+                        StatusCode::NOT_ACCEPTABLE,
+                        Json(ErrorResponse {
+                            error: String::from("Accept 標頭中沒有支援的格式")
+                        }),
+                    )
+                        .into_response();
+                },
+            },
+            _ => OutputFormat::default(),
+        },
+    };
 
-    ([(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))], json_string)
+    let mut cards = fetch_nhi_cards(fresh).await.unwrap_or_default();
+
+    lease::retain_visible(&mut cards, LeaseHolder {
+        lease_ids: &lease, owner: None
+    });
+
+    if let Some(audit_log) = state.audit_log.as_ref() {
+        audit_reads(audit_log, &cards, &audit_client(addr, &headers, "/"));
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(format.content_type())),
+            (header::VARY, HeaderValue::from_static("accept")),
+        ],
+        format.render(&cards),
+    )
+        .into_response()
 }
 
-pub async fn version_handler() -> impl IntoResponse {
-    static START: Once = Once::new();
-
-    START.call_once(|| unsafe {
-        VERSION = json!({
-            "text": env!("CARGO_PKG_VERSION"),
-            "major": env!("CARGO_PKG_VERSION_MAJOR").parse::<u32>().unwrap(),
-            "minor": env!("CARGO_PKG_VERSION_MINOR").parse::<u32>().unwrap(),
-            "patch": env!("CARGO_PKG_VERSION_PATCH").parse::<u32>().unwrap(),
-            "pre": env!("CARGO_PKG_VERSION_PRE"),
-        })
-        .to_string();
+/// Reads the cards of all types, tagged with `card_type`.
+pub async fn cards_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(FreshQuery {
+        fresh,
+        lease,
+    }): Query<FreshQuery>,
+) -> impl IntoResponse {
+    let mut cards = fetch_cards(fresh).await.unwrap_or_default();
+
+    lease::retain_visible(&mut cards, LeaseHolder {
+        lease_ids: &lease, owner: None
     });
 
-    ([(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))], unsafe {
-        VERSION.as_str()
+    if let Some(audit_log) = state.audit_log.as_ref() {
+        let client = audit_client(addr, &headers, "/cards");
+
+        for card in cards.iter() {
+            audit_log.record(
+                AuditEventKind::Read,
+                card.reader_name().map(String::from),
+                card.card_id(),
+                &client,
+            );
+        }
+    }
+
+    Json(cards)
+}
+
+pub async fn audit_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    if let Err(status_code) = check_admin_token(&state, &headers) {
+        return status_code.into_response();
+    }
+
+    let Some(audit_log) = state.audit_log.as_ref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match audit_log.query(query).await {
+        Ok(records) => Json(records).into_response(),
+        Err(error) => {
+            tracing::error!(target: "audit", ?error);
+
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FreshQuery {
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    fresh: bool,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    lease: Vec<String>,
+}
+
+pub async fn fhir_patient_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(FreshQuery {
+        fresh,
+        lease,
+    }): Query<FreshQuery>,
+) -> impl IntoResponse {
+    let mut cards = fetch_nhi_cards(fresh).await.unwrap_or_default();
+
+    lease::retain_visible(&mut cards, LeaseHolder {
+        lease_ids: &lease, owner: None
+    });
+
+    if let Some(audit_log) = state.audit_log.as_ref() {
+        audit_reads(audit_log, &cards, &audit_client(addr, &headers, "/fhir/Patient"));
+    }
+
+    let format = OutputFormat::Fhir;
+
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()))],
+        format.render(&cards),
+    )
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Hl7MessageType {
+    #[default]
+    Pid,
+    AdtA04,
+}
+
+#[derive(Deserialize)]
+struct Hl7Query {
+    #[serde(default, rename = "type")]
+    message_type: Hl7MessageType,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    fresh:        bool,
+    #[serde(default, deserialize_with = "deserialize_list")]
+    lease:        Vec<String>,
+}
+
+async fn hl7_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(Hl7Query {
+        message_type,
+        fresh,
+        lease,
+    }): Query<Hl7Query>,
+) -> impl IntoResponse {
+    let mut cards = fetch_nhi_cards(fresh).await.unwrap_or_default();
+
+    lease::retain_visible(&mut cards, LeaseHolder {
+        lease_ids: &lease, owner: None
+    });
+
+    if let Some(audit_log) = state.audit_log.as_ref() {
+        audit_reads(audit_log, &cards, &audit_client(addr, &headers, "/hl7"));
+    }
+
+    let body = match message_type {
+        Hl7MessageType::Pid => hl7::pid_segments(&cards),
+        Hl7MessageType::AdtA04 => hl7::adt_a04_messages(&cards),
+    };
+
+    ([(header::CONTENT_TYPE, HeaderValue::from_static(hl7::CONTENT_TYPE))], body)
+}
+
+/// The simulator endpoints are open unless an admin token is set, since the simulator is meant for development.
+fn check_simulator_access<'a>(
+    state: &'a AppState,
+    headers: &HeaderMap,
+) -> Result<&'a Simulator, StatusCode> {
+    let Some(simulator) = state.simulator.as_deref() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    if state.admin_token.is_some() {
+        check_admin_token(state, headers)?;
+    }
+
+    Ok(simulator)
+}
+
+#[inline]
+fn simulator_error_response(error: SimulatorError) -> Response {
+    let status_code = match error {
+        SimulatorError::UnknownReader(_) => StatusCode::NOT_FOUND,
+        SimulatorError::InvalidCard(_) => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+
+    (
+        status_code,
+        Json(ErrorResponse {
+            error: error.to_string()
+        }),
+    )
+        .into_response()
+}
+
+async fn sim_insert_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(card): Json<SimulatedCard>,
+) -> Response {
+    let simulator = match check_simulator_access(&state, &headers) {
+        Ok(simulator) => simulator,
+        Err(status_code) => return status_code.into_response(),
+    };
+
+    match simulator.insert(card) {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => simulator_error_response(error),
+    }
+}
+
+#[derive(Deserialize)]
+struct SimRemoveBody {
+    reader: Option<String>,
+}
+
+async fn sim_remove_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<SimRemoveBody>>,
+) -> Response {
+    let simulator = match check_simulator_access(&state, &headers) {
+        Ok(simulator) => simulator,
+        Err(status_code) => return status_code.into_response(),
+    };
+
+    let reader = body.and_then(|Json(body)| body.reader);
+
+    match simulator.remove(reader.as_deref()) {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => simulator_error_response(error),
+    }
+}
+
+#[derive(Deserialize)]
+struct LeaseQuery {
+    #[serde(default, deserialize_with = "deserialize_list")]
+    lease: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ReaderInfo {
+    name: String,
+    busy: bool,
+}
+
+/// Lists the readers found by the last read. The readers reserved by other clients or used by other applications are busy.
+async fn readers_handler(
+    Query(LeaseQuery {
+        lease,
+    }): Query<LeaseQuery>,
+) -> impl IntoResponse {
+    let status = card_subsystem_status();
+
+    let holder = LeaseHolder {
+        lease_ids: &lease, owner: None
+    };
+
+    let readers = status
+        .reader_names
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| {
+            let busy =
+                lease::is_reserved_for_others(&name, holder) || status.busy_readers.contains(&name);
+
+            ReaderInfo {
+                name,
+                busy,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Json(readers)
+}
+
+#[inline]
+fn lease_response(result: Result<LeaseInfo, LeaseError>) -> Response {
+    match result {
+        Ok(lease) => Json(lease).into_response(),
+        Err(error) => {
+            let status_code = match error {
+                LeaseError::Reserved => StatusCode::CONFLICT,
+                LeaseError::NotFound => StatusCode::NOT_FOUND,
+            };
+
+            (
+                status_code,
+                Json(ErrorResponse {
+                    error: error.to_string()
+                }),
+            )
+                .into_response()
+        },
+    }
+}
+
+async fn lease_acquire_handler(
+    State(state): State<AppState>,
+    Path(reader): Path<String>,
+    Query(LeaseQuery {
+        lease,
+    }): Query<LeaseQuery>,
+) -> Response {
+    lease_response(lease::acquire(&reader, state.lease_ttl, LeaseHolder {
+        lease_ids: &lease,
+        owner:     None,
+    }))
+}
+
+async fn lease_renew_handler(
+    State(state): State<AppState>,
+    Path(lease_id): Path<String>,
+) -> Response {
+    lease_response(lease::renew(&lease_id, state.lease_ttl))
+}
+
+async fn lease_release_handler(Path(lease_id): Path<String>) -> Response {
+    match lease::release(&lease_id) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => lease_response(Err(error)),
+    }
+}
+
+/// Checks the APDUs against the allowlist and transmits them to the card in the reader.
+async fn passthrough(
+    state: &AppState,
+    reader: String,
+    apdus: &[String],
+    client: &AuditClient,
+) -> Result<ApduResponse, PassthroughError> {
+    let apdus = parse_apdus(apdus, &state.apdu_allowlist)?;
+
+    if let Some(audit_log) = state.audit_log.as_ref() {
+        audit_log.record_apdus(reader.clone(), &apdus, client);
+    }
+
+    let responses = transmit_apdus(reader, apdus).await?;
+
+    Ok(ApduResponse {
+        responses: responses.iter().map(|response| ApduResult::from_response(response)).collect(),
     })
 }
 
+#[inline]
+fn passthrough_error_response(error: PassthroughError) -> Response {
+    let status_code = match error {
+        PassthroughError::InvalidApdu(_) => StatusCode::BAD_REQUEST,
+        PassthroughError::NotAllowed(_) => StatusCode::FORBIDDEN,
+        PassthroughError::Busy => StatusCode::CONFLICT,
+        PassthroughError::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+        PassthroughError::Pcsc(
+            pcsc::Error::UnknownReader | pcsc::Error::NoSmartcard | pcsc::Error::RemovedCard,
+        ) => StatusCode::NOT_FOUND,
+        PassthroughError::Pcsc(_) => StatusCode::BAD_GATEWAY,
+    };
+
+    (
+        status_code,
+        Json(ErrorResponse {
+            error: error.to_string()
+        }),
+    )
+        .into_response()
+}
+
+async fn apdu_handler(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(reader): Path<String>,
+    Query(LeaseQuery {
+        lease,
+    }): Query<LeaseQuery>,
+    Json(ApduRequest {
+        apdus,
+    }): Json<ApduRequest>,
+) -> Response {
+    if let Err(status_code) = check_admin_token(&state, &headers) {
+        return status_code.into_response();
+    }
+
+    if lease::is_reserved_for_others(&reader, LeaseHolder {
+        lease_ids: &lease, owner: None
+    }) {
+        return lease_response(Err(LeaseError::Reserved));
+    }
+
+    let client = audit_client(addr, &headers, "/readers/{name}/apdu");
+
+    match passthrough(&state, reader, &apdus, &client).await {
+        Ok(response) => Json(response).into_response(),
+        Err(error) => passthrough_error_response(error),
+    }
+}
+
+pub async fn version_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))], VERSION.as_str())
+}
+
+pub async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let probe = match state.simulator.as_ref() {
+        Some(simulator) => Ok(simulator.reader_count()),
+        None => probe_pcsc().await,
+    };
+    let card_status = card_subsystem_status();
+
+    let (status_code, status) = match probe {
+        Ok(0) => (StatusCode::OK, "degraded"),
+        Ok(_) if !card_status.timed_out_readers.is_empty() => (StatusCode::OK, "degraded"),
+        Ok(_) => (StatusCode::OK, "ok"),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "down"),
+    };
+
+    let body = json!({
+        "status": status,
+        "checks": {
+            "context_established": card_status.context_established,
+            "pcscd_reachable": probe.is_ok(),
+            "reader_count": probe
+                .as_ref()
+                .ok()
+                .copied()
+                .or(card_status.reader_names.as_ref().map(|names| names.len())),
+            "last_success_time": card_status.last_success_time,
+            "timed_out_readers": card_status.timed_out_readers,
+            "busy_readers": card_status.busy_readers,
+            "last_error": match probe {
+                Err(error) => Some(error.to_string()),
+                Ok(_) => card_status.last_error,
+            },
+        },
+    });
+
+    (
+        status_code,
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+        body.to_string(),
+    )
+}
+
+pub async fn metrics_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, HeaderValue::from_static(prometheus::TEXT_FORMAT))], metrics::encode())
+}
+
+async fn track_http_requests(
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+
+    let route = matched_path.as_ref().map(|p| p.as_str()).unwrap_or("unknown");
+
+    metrics::HTTP_REQUESTS.with_label_values(&[route, response.status().as_str()]).inc();
+
+    response
+}
+
 fn create_app(state: AppState) -> Router {
-    Router::new()
+    let mut router = Router::new()
         .route("/", get(index_handler))
         .route("/ws", get(ws_handler))
+        .route("/cards", get(cards_handler))
+        .route("/fhir/Patient", get(fhir_patient_handler))
+        .route("/hl7", get(hl7_handler))
         .route("/version", get(version_handler))
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/audit", get(audit_handler))
+        .route("/readers", get(readers_handler))
+        .route("/readers/:name/lease", post(lease_acquire_handler))
+        .route("/leases/:id", put(lease_renew_handler).delete(lease_release_handler));
+
+    if state.simulator.is_some() {
+        router = router
+            .route("/sim/insert", post(sim_insert_handler))
+            .route("/sim/remove", post(sim_remove_handler));
+    }
+
+    if !state.apdu_allowlist.is_empty() {
+        router = router.route("/readers/:name/apdu", post(apdu_handler));
+    }
+
+    router
+        .route_layer(middleware::from_fn(track_http_requests))
         .layer(CorsLayer::permissive())
         .layer(SetResponseHeaderLayer::overriding(
             header::CACHE_CONTROL,
@@ -272,7 +1034,7 @@ pub async fn server_main(socket_addr: SocketAddr, state: AppState) -> anyhow::Re
 
     let listener = tokio::net::TcpListener::bind(socket_addr).await?;
     tracing::info!("listening on http://{socket_addr}");
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
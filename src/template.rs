@@ -0,0 +1,167 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    card::{NHICardBasic, NHICardDerived},
+    format::fields,
+};
+
+#[derive(Debug)]
+pub enum TemplateError {
+    UnknownField(String),
+    UnclosedBrace,
+}
+
+impl Display for TemplateError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownField(name) => {
+                write!(
+                    f,
+                    "未知的欄位 {name:?}，可用的欄位有 {}",
+                    field_names().collect::<Vec<_>>().join(", ")
+                )
+            },
+            Self::UnclosedBrace => f.write_str("模板中有未關閉的 {"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// The derived fields can always be used in templates.
+#[inline]
+fn field_names() -> impl Iterator<Item = &'static str> {
+    NHICardBasic::FIELD_NAMES.iter().chain(NHICardDerived::FIELD_NAMES).copied()
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Field(&'static str),
+}
+
+/// A template such as `{id_no}\t{full_name}\n` which is rendered with the fields of a card.
+///
+/// `\t`, `\n`, `\r` and `\\` are unescaped so that they can be passed from the command line. Use `{{` and `}}` for literal braces.
+#[derive(Debug, Clone)]
+pub struct CardTemplate {
+    parts: Vec<Part>,
+}
+
+impl CardTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t');
+                    },
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n');
+                    },
+                    Some('r') => {
+                        chars.next();
+                        literal.push('\r');
+                    },
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\');
+                    },
+                    _ => literal.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(TemplateError::UnclosedBrace),
+                        }
+                    }
+
+                    let name = name.trim();
+
+                    let Some(name) = field_names().find(|&n| n == name) else {
+                        return Err(TemplateError::UnknownField(String::from(name)));
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+
+                    parts.push(Part::Field(name));
+                },
+                _ => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self {
+            parts,
+        })
+    }
+
+    pub fn render(&self, card: &NHICardBasic) -> String {
+        let fields = fields(&card.with_derived());
+
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(s) => s.as_str(),
+                Part::Field(name) => fields
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, value)| value.as_str())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let card = NHICardBasic::builder().build().unwrap();
+
+        let template = CardTemplate::parse("{id_no}\\t{ full_name }\\n").unwrap();
+        assert_eq!("A123456789\t測試\n", template.render(&card));
+
+        let template = CardTemplate::parse("{{{card_no}}}\\r\\\\x\\q").unwrap();
+        assert_eq!("{000000000000}\r\\x\\q", template.render(&card));
+
+        let template = CardTemplate::parse("{birth_date_roc}").unwrap();
+        assert_eq!("0890101", template.render(&card));
+
+        assert!(matches!(
+            CardTemplate::parse("{id_no}{name}"),
+            Err(TemplateError::UnknownField(name)) if name == "name"
+        ));
+        assert!(matches!(CardTemplate::parse("{id_no"), Err(TemplateError::UnclosedBrace)));
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{sync::broadcast::error::RecvError, task, time};
+
+use crate::{
+    card::{subscribe_card_events, CardEvent, CardEventKind, NHICardBasic},
+    server::is_reader_reserved,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub urls:        Vec<String>,
+    pub secret:      Option<String>,
+    pub mask:        bool,
+    pub max_retries: u32,
+    pub dead_letter: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    event:       CardEventKind,
+    time:        DateTime<Local>,
+    reader_name: &'a str,
+    card:        &'a NHICardBasic,
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    time:    DateTime<Local>,
+    url:     &'a str,
+    error:   String,
+    payload: serde_json::Value,
+}
+
+#[derive(Debug)]
+pub struct Webhook {
+    config:           WebhookConfig,
+    client:           reqwest::Client,
+    dead_letter_lock: Mutex<()>,
+}
+
+#[inline]
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("tw-nhi-icc-service/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            config,
+            client,
+            dead_letter_lock: Mutex::new(()),
+        })
+    }
+
+    fn build_body(&self, event: &CardEvent) -> Vec<u8> {
+        let masked;
+
+        let card = if self.config.mask {
+            masked = event.card.masked();
+
+            &masked
+        } else {
+            &event.card
+        };
+
+        serde_json::to_vec(&WebhookPayload {
+            event: event.kind,
+            time: event.time,
+            reader_name: &event.reader_name,
+            card,
+        })
+        .unwrap()
+    }
+
+    async fn post(&self, url: &str, body: &[u8]) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+
+        if let Some(secret) = self.config.secret.as_deref() {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body));
+        }
+
+        request.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn deliver(&self, url: &str, body: &[u8]) {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+
+        loop {
+            match self.post(url, body).await {
+                Ok(_) => {
+                    tracing::debug!(target: "webhook", url, attempt, "delivered");
+
+                    return;
+                },
+                Err(error) if attempt < self.config.max_retries => {
+                    tracing::warn!(target: "webhook", url, attempt, ?error, "delivery failed, retrying");
+
+                    time::sleep(backoff).await;
+
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                },
+                Err(error) => {
+                    tracing::error!(target: "webhook", url, attempt, ?error, "delivery failed");
+
+                    self.write_dead_letter(url, body, error.to_string());
+
+                    return;
+                },
+            }
+        }
+    }
+
+    fn write_dead_letter(&self, url: &str, body: &[u8], error: String) {
+        let Some(path) = self.config.dead_letter.as_ref() else {
+            return;
+        };
+
+        let dead_letter = DeadLetter {
+            time: Local::now(),
+            url,
+            error,
+            payload: serde_json::from_slice(body).unwrap(),
+        };
+
+        let mut line = serde_json::to_vec(&dead_letter).unwrap();
+        line.push(b'\n');
+
+        let _lock = self.dead_letter_lock.lock().unwrap();
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&line));
+
+        if let Err(error) = result {
+            tracing::error!(target: "webhook", ?error, "cannot write the dead letter");
+        }
+    }
+
+    pub fn spawn_background_tasks(self: &Arc<Self>) {
+        let webhook = self.clone();
+
+        task::spawn(async move {
+            let mut receiver = subscribe_card_events();
+
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if is_reader_reserved(&event.reader_name) => (),
+                    Ok(event) => {
+                        let body: Arc<[u8]> = webhook.build_body(&event).into();
+
+                        for index in 0..webhook.config.urls.len() {
+                            let webhook = webhook.clone();
+                            let body = body.clone();
+
+                            task::spawn(async move {
+                                webhook.deliver(&webhook.config.urls[index], &body).await
+                            });
+                        }
+                    },
+                    Err(RecvError::Lagged(count)) => {
+                        tracing::warn!(target: "webhook", count, "card events lagged");
+                    },
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}